@@ -1 +1,2 @@
+pub mod options;
 pub mod uci;
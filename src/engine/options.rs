@@ -0,0 +1,230 @@
+//! Engine identification and option declarations, as sent in reply to "uci".
+
+/// Words that start a field of an option declaration.
+const OPTION_KEYWORDS: [&str; 6] = ["name", "type", "default", "min", "max", "var"];
+
+/// Type of an engine option, with its default value and constraints.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OptionKind {
+    /// A whole number between `min` and `max`.
+    Spin { default: i64, min: i64, max: i64 },
+    /// A switch.
+    Check { default: bool },
+    /// One of a fixed set of strings.
+    Combo {
+        default: String,
+        choices: Vec<String>,
+    },
+    /// Free text, often a file path.
+    String { default: String },
+    /// An action without a value.
+    Button,
+}
+
+/// An option declared by an engine.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EngineOption {
+    pub name: String,
+    pub kind: OptionKind,
+}
+
+impl EngineOption {
+    /// Parses a declaration such as "option name Hash type spin default 16 min 1 max 33554432".
+    /// Returns None for other lines and for declarations of unknown types.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("option") {
+            return None;
+        }
+
+        let mut fields: Vec<(&str, Vec<&str>)> = Vec::new();
+        for token in tokens {
+            if OPTION_KEYWORDS.contains(&token) {
+                fields.push((token, Vec::new()));
+            } else if let Some((_, words)) = fields.last_mut() {
+                words.push(token);
+            }
+        }
+        let field = |key: &str| {
+            fields
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, words)| words.join(" "))
+        };
+        // UCI writes an empty string default as "<empty>".
+        let text = |key: &str| {
+            field(key)
+                .filter(|value| value != "<empty>")
+                .unwrap_or_default()
+        };
+
+        let name = field("name").filter(|name| !name.is_empty())?;
+        let kind = match field("type")?.as_str() {
+            "spin" => OptionKind::Spin {
+                default: field("default")?.parse().ok()?,
+                min: field("min")?.parse().ok()?,
+                max: field("max")?.parse().ok()?,
+            },
+            "check" => OptionKind::Check {
+                default: field("default")? == "true",
+            },
+            "combo" => OptionKind::Combo {
+                default: text("default"),
+                choices: fields
+                    .iter()
+                    .filter(|(k, _)| *k == "var")
+                    .map(|(_, words)| words.join(" "))
+                    .collect(),
+            },
+            "string" => OptionKind::String {
+                default: text("default"),
+            },
+            "button" => OptionKind::Button,
+            _ => return None,
+        };
+        Some(Self { name, kind })
+    }
+
+    /// Returns the value to send for `value`: spin values are clamped to the declared range,
+    /// and None is returned for values the engine would reject, such as a choice it does not
+    /// offer, and for buttons.
+    pub fn accepted_value(&self, value: &str) -> Option<String> {
+        match &self.kind {
+            OptionKind::Spin { min, max, .. } => {
+                let value: i64 = value.trim().parse().ok()?;
+                Some(value.clamp(*min, (*max).max(*min)).to_string())
+            }
+            OptionKind::Check { .. } => match value {
+                "true" | "false" => Some(value.to_string()),
+                _ => None,
+            },
+            OptionKind::Combo { choices, .. } => choices
+                .iter()
+                .find(|choice| choice.eq_ignore_ascii_case(value))
+                .cloned(),
+            OptionKind::String { .. } => Some(value.to_string()),
+            OptionKind::Button => None,
+        }
+    }
+
+    /// Returns the default value as sent with setoption, or None for a button.
+    pub fn default_value(&self) -> Option<String> {
+        match &self.kind {
+            OptionKind::Spin { default, .. } => Some(default.to_string()),
+            OptionKind::Check { default } => Some(default.to_string()),
+            OptionKind::Combo { default, .. } | OptionKind::String { default } => {
+                Some(default.clone())
+            }
+            OptionKind::Button => None,
+        }
+    }
+}
+
+/// What an engine says about itself: its name, author and options.
+#[derive(Clone, Debug, Default)]
+pub struct EngineInfo {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub options: Vec<EngineOption>,
+}
+
+impl EngineInfo {
+    /// Collects the "id" and "option" lines of the reply to "uci".
+    pub fn parse(lines: &[String]) -> Self {
+        let mut info = EngineInfo::default();
+        for line in lines {
+            if let Some(name) = line.strip_prefix("id name ") {
+                info.name = Some(name.trim().to_string());
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                info.author = Some(author.trim().to_string());
+            } else if let Some(option) = EngineOption::parse(line) {
+                info.options.push(option);
+            }
+        }
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_reply_to_uci() {
+        let lines = [
+            "id name Pikafish 2024",
+            "id author the Pikafish developers",
+            "option name Threads type spin default 1 min 1 max 1024",
+            "option name Ponder type check default false",
+            "option name Clear Hash type button",
+            "option name EvalFile type string default pikafish.nnue",
+            "option name Debug Log File type string default <empty>",
+            "option name Style type combo default Normal var Solid var Normal var Risky Play",
+            "option name Odd type color default red",
+            "uciok",
+        ]
+        .map(String::from);
+        let info = EngineInfo::parse(&lines);
+        assert_eq!(info.name.as_deref(), Some("Pikafish 2024"));
+        assert_eq!(info.author.as_deref(), Some("the Pikafish developers"));
+
+        let kinds: Vec<(&str, &OptionKind)> = info
+            .options
+            .iter()
+            .map(|o| (o.name.as_str(), &o.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (
+                    "Threads",
+                    &OptionKind::Spin {
+                        default: 1,
+                        min: 1,
+                        max: 1024
+                    }
+                ),
+                ("Ponder", &OptionKind::Check { default: false }),
+                ("Clear Hash", &OptionKind::Button),
+                (
+                    "EvalFile",
+                    &OptionKind::String {
+                        default: "pikafish.nnue".to_string()
+                    }
+                ),
+                (
+                    "Debug Log File",
+                    &OptionKind::String {
+                        default: String::new()
+                    }
+                ),
+                (
+                    "Style",
+                    &OptionKind::Combo {
+                        default: "Normal".to_string(),
+                        choices: vec![
+                            "Solid".to_string(),
+                            "Normal".to_string(),
+                            "Risky Play".to_string()
+                        ],
+                    }
+                ),
+            ]
+        );
+        assert_eq!(info.options[0].default_value().as_deref(), Some("1"));
+        assert_eq!(info.options[2].default_value(), None);
+
+        let threads = &info.options[0];
+        assert_eq!(threads.accepted_value("8").as_deref(), Some("8"));
+        assert_eq!(threads.accepted_value("4096").as_deref(), Some("1024"));
+        assert_eq!(threads.accepted_value("0").as_deref(), Some("1"));
+        assert_eq!(threads.accepted_value("many"), None);
+        assert_eq!(info.options[1].accepted_value("yes"), None);
+        let style = &info.options[5];
+        assert_eq!(
+            style.accepted_value("risky play").as_deref(),
+            Some("Risky Play")
+        );
+        assert_eq!(style.accepted_value("Wild"), None);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::engine::options::EngineInfo;
+use std::collections::BTreeMap;
 use std::io::{BufRead, BufReader, Write};
 use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
 use std::sync::mpsc::{self, Receiver, Sender};
@@ -150,6 +152,11 @@ impl UciEngine {
         self.send_command(&cmd)
     }
 
+    /// Sends a setoption command for a button option, which takes no value.
+    pub fn press_button(&mut self, name: &str) -> Result<(), EngineError> {
+        self.send_command(&format!("setoption name {}", name))
+    }
+
     /// Sends the position command starting from initial position with moves.
     pub fn set_position_startpos(&mut self, moves: &str) -> Result<(), EngineError> {
         let cmd = if moves.is_empty() {
@@ -160,34 +167,57 @@ impl UciEngine {
         self.send_command(&cmd)
     }
 
+    /// Sends the position command starting from a FEN position with moves.
+    pub fn set_position_fen(&mut self, fen: &str, moves: &str) -> Result<(), EngineError> {
+        let cmd = if moves.is_empty() {
+            format!("position fen {}", fen)
+        } else {
+            format!("position fen {} moves {}", fen, moves)
+        };
+        self.send_command(&cmd)
+    }
+
     /// Sends "go" command and waits for "bestmove" response.
-    /// Returns the best move in UCI format (e.g., "e2e4").
-    pub fn go(
-        &mut self,
-        depth: Option<u32>,
-        movetime_ms: Option<u64>,
-    ) -> Result<String, EngineError> {
-        let mut cmd = "go".to_string();
-        if let Some(d) = depth {
-            cmd.push_str(&format!(" depth {}", d));
-        }
-        if let Some(t) = movetime_ms {
-            cmd.push_str(&format!(" movetime {}", t));
-        }
-        self.send_command(&cmd)?;
+    /// Returns the best move in UCI format (e.g., "e2e4") and the last score reported.
+    pub fn go(&mut self, limits: &SearchLimits) -> Result<SearchResult, EngineError> {
+        self.send_command(&limits.command())?;
+
+        let lines =
+            self.read_until_timeout(|line| line.starts_with("bestmove"), limits.timeout())?;
 
-        let lines = self.read_until_timeout(
+        let best_move = match lines.iter().rev().find(|line| line.starts_with("bestmove")) {
+            Some(line) => Self::parse_bestmove(line)?,
+            None => return Err(EngineError::EngineNotReady),
+        };
+        let score = lines
+            .iter()
+            .rev()
+            .find_map(|line| SearchInfo::parse(line)?.score);
+        Ok(SearchResult { best_move, score })
+    }
+
+    /// Starts searching until told to stop.
+    pub fn go_infinite(&mut self) -> Result<(), EngineError> {
+        self.send_command("go infinite")
+    }
+
+    /// Stops the search and waits for its "bestmove" response.
+    pub fn stop(&mut self) -> Result<(), EngineError> {
+        self.send_command("stop")?;
+        self.read_until_timeout(
             |line| line.starts_with("bestmove"),
-            std::time::Duration::from_secs(30),
+            std::time::Duration::from_secs(5),
         )?;
+        Ok(())
+    }
 
-        for line in lines.iter().rev() {
-            if line.starts_with("bestmove") {
-                return Self::parse_bestmove(line);
-            }
+    /// Reads the next line from the engine, or None if none arrives within the timeout.
+    pub fn read_line(&self, timeout: std::time::Duration) -> Result<Option<String>, EngineError> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(line) => Ok(Some(line)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(EngineError::EngineClosed),
         }
-
-        Err(EngineError::EngineNotReady)
     }
 
     /// Parses the bestmove line and extracts the move.
@@ -201,42 +231,242 @@ impl UciEngine {
     }
 }
 
+/// An evaluation reported by the engine, from the point of view of the side to move.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Score {
+    /// Advantage in centipawns.
+    Centipawns(i32),
+    /// Mate in this many moves; negative if the side to move is getting mated.
+    Mate(i32),
+}
+
+impl Score {
+    /// Centipawns below which the engine agrees to a draw.
+    const DRAW_ACCEPT_THRESHOLD: i32 = -50;
+
+    /// Returns true if a side with this score would rather take a draw than play on.
+    pub fn accepts_draw(&self) -> bool {
+        match *self {
+            Score::Centipawns(cp) => cp <= Self::DRAW_ACCEPT_THRESHOLD,
+            Score::Mate(moves) => moves < 0,
+        }
+    }
+}
+
+/// Progress of a search, parsed from an info line. Fields the line does not mention are None.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchInfo {
+    /// Rank of the line among the best lines searched, 1 being the best.
+    pub multipv: Option<u32>,
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub score: Option<Score>,
+    pub nodes: Option<u64>,
+    /// Nodes searched per second.
+    pub nps: Option<u64>,
+    /// Time searched, in milliseconds.
+    pub time_ms: Option<u64>,
+    /// Principal variation, in UCI format.
+    pub pv: Vec<String>,
+}
+
+impl SearchInfo {
+    /// Parses an info line, e.g. "info depth 12 score cp 35 nodes 81234 pv h2e2 h9g7".
+    /// Returns None for other lines and for info lines carrying none of these fields.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("info") {
+            return None;
+        }
+        let mut info = SearchInfo::default();
+        while let Some(token) = tokens.next() {
+            match token {
+                "multipv" => info.multipv = tokens.next().and_then(|t| t.parse().ok()),
+                "depth" => info.depth = tokens.next().and_then(|t| t.parse().ok()),
+                "seldepth" => info.seldepth = tokens.next().and_then(|t| t.parse().ok()),
+                "nodes" => info.nodes = tokens.next().and_then(|t| t.parse().ok()),
+                "nps" => info.nps = tokens.next().and_then(|t| t.parse().ok()),
+                "time" => info.time_ms = tokens.next().and_then(|t| t.parse().ok()),
+                "score" => {
+                    let kind = tokens.next();
+                    let value = tokens.next().and_then(|t| t.parse().ok());
+                    info.score = match (kind, value) {
+                        (Some("cp"), Some(cp)) => Some(Score::Centipawns(cp)),
+                        (Some("mate"), Some(moves)) => Some(Score::Mate(moves)),
+                        _ => None,
+                    };
+                }
+                "pv" => {
+                    info.pv = tokens.map(str::to_string).collect();
+                    break;
+                }
+                // The rest of the line is free text.
+                "string" => break,
+                _ => {}
+            }
+        }
+        (info != SearchInfo::default()).then_some(info)
+    }
+
+    /// Takes in the fields reported by a later info line of the same search.
+    pub fn update(&mut self, later: SearchInfo) {
+        self.multipv = later.multipv.or(self.multipv);
+        self.depth = later.depth.or(self.depth);
+        self.seldepth = later.seldepth.or(self.seldepth);
+        self.score = later.score.or(self.score);
+        self.nodes = later.nodes.or(self.nodes);
+        self.nps = later.nps.or(self.nps);
+        self.time_ms = later.time_ms.or(self.time_ms);
+        if !later.pv.is_empty() {
+            self.pv = later.pv;
+        }
+    }
+}
+
+/// What an analysis has found so far: the statistics of the search as a whole, and the
+/// latest score and variation of each line keyed by its MultiPV rank.
+#[derive(Clone, Debug, Default)]
+pub struct SearchProgress {
+    pub stats: SearchInfo,
+    pub lines: BTreeMap<u32, SearchInfo>,
+}
+
+impl SearchProgress {
+    pub fn update(&mut self, info: SearchInfo) {
+        if info.score.is_some() || !info.pv.is_empty() {
+            self.lines
+                .entry(info.multipv.unwrap_or(1))
+                .or_default()
+                .update(info.clone());
+        }
+        self.stats.update(SearchInfo {
+            multipv: None,
+            score: None,
+            pv: Vec::new(),
+            ..info
+        });
+    }
+}
+
+/// Outcome of a search.
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    /// The move chosen, in UCI format.
+    pub best_move: String,
+    /// The last score reported before the move, if any.
+    pub score: Option<Score>,
+}
+
+/// Limits of a search, sent with the go command. Times are in milliseconds; `w` stands for
+/// Red and `b` for Black.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub movetime_ms: Option<u64>,
+    pub wtime_ms: Option<u64>,
+    pub btime_ms: Option<u64>,
+    pub winc_ms: Option<u64>,
+    pub binc_ms: Option<u64>,
+    /// Moves to play before the next time control.
+    pub movestogo: Option<u32>,
+}
+
+impl SearchLimits {
+    /// Returns the go command for these limits.
+    pub fn command(&self) -> String {
+        let mut cmd = "go".to_string();
+        let fields = [
+            ("depth", self.depth.map(u64::from)),
+            ("movetime", self.movetime_ms),
+            ("wtime", self.wtime_ms),
+            ("btime", self.btime_ms),
+            ("winc", self.winc_ms),
+            ("binc", self.binc_ms),
+            ("movestogo", self.movestogo.map(u64::from)),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value {
+                cmd.push_str(&format!(" {} {}", name, value));
+            }
+        }
+        cmd
+    }
+
+    /// Returns how long to wait for the best move: the most time the search may use, plus a
+    /// margin, and never less than 30 seconds.
+    fn timeout(&self) -> std::time::Duration {
+        let longest = [self.movetime_ms, self.wtime_ms, self.btime_ms]
+            .into_iter()
+            .flatten()
+            .max()
+            .unwrap_or(0);
+        std::time::Duration::from_millis(longest + 5000).max(std::time::Duration::from_secs(30))
+    }
+}
+
 /// Request sent to the engine thread.
+pub enum EngineRequest {
+    /// Search for a move to play.
+    Move(MoveRequest),
+    /// Analyse a position until the next request.
+    Analyze(AnalysisRequest),
+    /// Stop analysing.
+    Stop,
+    /// Set an option, or press it if it is a button and has no value.
+    SetOption { name: String, value: Option<String> },
+}
+
+/// A position to analyse.
+pub struct AnalysisRequest {
+    /// Tags the info sent back, so that info from earlier positions can be told apart.
+    pub id: u64,
+    /// FEN of the root position, or None for the standard opening.
+    pub start_fen: Option<String>,
+    pub moves_uci: String,
+    /// Number of best lines to search.
+    pub multipv: u32,
+}
+
+/// A search for a move to play.
 pub struct MoveRequest {
+    /// FEN of the root position, or None for the standard opening.
+    pub start_fen: Option<String>,
     pub moves_uci: String,
-    pub depth: Option<u32>,
-    pub movetime_ms: Option<u64>,
+    pub limits: SearchLimits,
     pub elo: Option<u32>,
 }
 
 /// A handle to communicate with a running engine in a background thread.
 pub struct EngineHandle {
-    request_sender: Sender<MoveRequest>,
-    result_receiver: Receiver<Result<String, EngineError>>,
+    request_sender: Sender<EngineRequest>,
+    result_receiver: Receiver<Result<SearchResult, EngineError>>,
+    info_receiver: Receiver<(u64, SearchInfo)>,
+    info: EngineInfo,
 }
 
 impl EngineHandle {
     /// Spawns a new engine in a background thread.
     /// The engine is initialized (uci, isready) before this function returns.
     pub fn new(engine_path: &str) -> Result<Self, EngineError> {
-        let (request_sender, request_receiver) = mpsc::channel::<MoveRequest>();
-        let (result_sender, result_receiver) = mpsc::channel::<Result<String, EngineError>>();
+        let (request_sender, request_receiver) = mpsc::channel::<EngineRequest>();
+        let (result_sender, result_receiver) = mpsc::channel::<Result<SearchResult, EngineError>>();
+        let (info_sender, info_receiver) = mpsc::channel::<(u64, SearchInfo)>();
 
         let path = engine_path.to_string();
 
-        let (init_sender, init_receiver) = mpsc::channel::<Result<(), EngineError>>();
+        let (init_sender, init_receiver) = mpsc::channel::<Result<EngineInfo, EngineError>>();
 
         thread::spawn(move || {
             let engine_result = (|| {
                 let mut engine = UciEngine::new(&path)?;
-                engine.init_uci()?;
+                let lines = engine.init_uci()?;
                 engine.wait_ready()?;
-                Ok::<UciEngine, EngineError>(engine)
+                Ok::<_, EngineError>((engine, EngineInfo::parse(&lines)))
             })();
 
             let mut engine = match engine_result {
-                Ok(e) => {
-                    let _ = init_sender.send(Ok(()));
+                Ok((e, info)) => {
+                    let _ = init_sender.send(Ok(info));
                     e
                 }
                 Err(e) => {
@@ -245,53 +475,158 @@ impl EngineHandle {
                 }
             };
 
-            while let Ok(request) = request_receiver.recv() {
-                let result = (|| {
-                    engine.new_game()?;
-                    if let Some(elo) = request.elo {
-                        engine.set_option("UCI_LimitStrength", "true")?;
-                        engine.set_option("UCI_Elo", &elo.to_string())?;
-                    } else {
-                        engine.set_option("UCI_LimitStrength", "false")?;
+            let mut next = None;
+            loop {
+                let request = match next.take() {
+                    Some(request) => request,
+                    None => match request_receiver.recv() {
+                        Ok(request) => request,
+                        Err(_) => break,
+                    },
+                };
+                match request {
+                    EngineRequest::Move(request) => {
+                        let result = (|| {
+                            engine.new_game()?;
+                            engine.set_option("MultiPV", "1")?;
+                            if let Some(elo) = request.elo {
+                                engine.set_option("UCI_LimitStrength", "true")?;
+                                engine.set_option("UCI_Elo", &elo.to_string())?;
+                            } else {
+                                engine.set_option("UCI_LimitStrength", "false")?;
+                            }
+                            match &request.start_fen {
+                                Some(fen) => engine.set_position_fen(fen, &request.moves_uci)?,
+                                None => engine.set_position_startpos(&request.moves_uci)?,
+                            }
+                            engine.go(&request.limits)
+                        })();
+                        if result_sender.send(result).is_err() {
+                            break;
+                        }
+                    }
+                    EngineRequest::Analyze(request) => {
+                        let started = (|| {
+                            engine.set_option("UCI_LimitStrength", "false")?;
+                            engine.set_option("MultiPV", &request.multipv.to_string())?;
+                            match &request.start_fen {
+                                Some(fen) => engine.set_position_fen(fen, &request.moves_uci)?,
+                                None => engine.set_position_startpos(&request.moves_uci)?,
+                            }
+                            engine.go_infinite()
+                        })();
+                        if started.is_err() {
+                            continue;
+                        }
+
+                        // Pass on what the search finds until the next request arrives.
+                        loop {
+                            match request_receiver.try_recv() {
+                                Ok(request) => {
+                                    next = Some(request);
+                                    break;
+                                }
+                                Err(mpsc::TryRecvError::Empty) => {}
+                                Err(mpsc::TryRecvError::Disconnected) => break,
+                            }
+                            match engine.read_line(std::time::Duration::from_millis(50)) {
+                                Ok(Some(line)) => {
+                                    if let Some(info) = SearchInfo::parse(&line)
+                                        && info_sender.send((request.id, info)).is_err()
+                                    {
+                                        break;
+                                    }
+                                }
+                                Ok(None) => {}
+                                Err(_) => break,
+                            }
+                        }
+                        let _ = engine.stop();
+                    }
+                    EngineRequest::Stop => {}
+                    EngineRequest::SetOption { name, value } => {
+                        let _ = match value {
+                            Some(value) => engine.set_option(&name, &value),
+                            None => engine.press_button(&name),
+                        };
                     }
-                    engine.set_position_startpos(&request.moves_uci)?;
-                    engine.go(request.depth, request.movetime_ms)
-                })();
-                if result_sender.send(result).is_err() {
-                    break;
                 }
             }
         });
 
-        init_receiver
+        let info = init_receiver
             .recv()
             .map_err(|_| EngineError::EngineClosed)??;
 
         Ok(Self {
             request_sender,
             result_receiver,
+            info_receiver,
+            info,
         })
     }
 
+    /// Returns the engine's name, author and options, as declared at start-up.
+    pub fn info(&self) -> &EngineInfo {
+        &self.info
+    }
+
+    /// Sets an option, or presses it if `value` is None (non-blocking). Interrupts any
+    /// analysis in progress.
+    pub fn set_option(&self, name: String, value: Option<String>) {
+        let _ = self
+            .request_sender
+            .send(EngineRequest::SetOption { name, value });
+    }
+
     /// Sends a move request to the engine (non-blocking).
     pub fn request_move(
         &self,
+        start_fen: Option<String>,
         moves_uci: String,
-        depth: Option<u32>,
-        movetime_ms: Option<u64>,
+        limits: SearchLimits,
         elo: Option<u32>,
     ) {
-        let _ = self.request_sender.send(MoveRequest {
+        let _ = self.request_sender.send(EngineRequest::Move(MoveRequest {
+            start_fen,
             moves_uci,
-            depth,
-            movetime_ms,
+            limits,
             elo,
-        });
+        }));
+    }
+
+    /// Starts analysing the best `multipv` lines of a position, replacing any analysis in
+    /// progress (non-blocking).
+    pub fn start_analysis(
+        &self,
+        id: u64,
+        start_fen: Option<String>,
+        moves_uci: String,
+        multipv: u32,
+    ) {
+        let _ = self
+            .request_sender
+            .send(EngineRequest::Analyze(AnalysisRequest {
+                id,
+                start_fen,
+                moves_uci,
+                multipv,
+            }));
+    }
+
+    /// Stops the analysis in progress (non-blocking).
+    pub fn stop_analysis(&self) {
+        let _ = self.request_sender.send(EngineRequest::Stop);
+    }
+
+    /// Tries to receive the progress of an analysis, tagged with its id (non-blocking).
+    pub fn try_recv_info(&self) -> Option<(u64, SearchInfo)> {
+        self.info_receiver.try_recv().ok()
     }
 
     /// Tries to receive a move result (non-blocking).
     /// Returns None if no result is available yet.
-    pub fn try_recv_move(&self) -> Option<Result<String, EngineError>> {
+    pub fn try_recv_move(&self) -> Option<Result<SearchResult, EngineError>> {
         self.result_receiver.try_recv().ok()
     }
 }
@@ -301,3 +636,56 @@ impl Drop for UciEngine {
         let _ = self.quit();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_info_lines() {
+        let info = SearchInfo::parse(
+            "info depth 12 seldepth 18 multipv 1 score cp -35 upperbound nodes 81234 \
+             nps 406170 time 200 pv h2e2 h9g7",
+        )
+        .unwrap();
+        assert_eq!(info.multipv, Some(1));
+        assert_eq!(info.depth, Some(12));
+        assert_eq!(info.seldepth, Some(18));
+        assert_eq!(info.score, Some(Score::Centipawns(-35)));
+        assert_eq!(info.nodes, Some(81234));
+        assert_eq!(info.nps, Some(406170));
+        assert_eq!(info.time_ms, Some(200));
+        assert_eq!(info.pv, vec!["h2e2", "h9g7"]);
+
+        let mut merged = info.clone();
+        merged.update(SearchInfo::parse("info depth 13 score mate -4").unwrap());
+        assert_eq!(merged.depth, Some(13));
+        assert_eq!(merged.score, Some(Score::Mate(-4)));
+        assert_eq!(merged.nodes, info.nodes);
+        assert_eq!(merged.pv, info.pv);
+
+        assert_eq!(SearchInfo::parse("info string NNUE enabled"), None);
+        assert_eq!(SearchInfo::parse("bestmove h2e2"), None);
+    }
+
+    #[test]
+    fn keeps_one_line_per_multipv_rank() {
+        let mut progress = SearchProgress::default();
+        for line in [
+            "info depth 5 multipv 1 score cp 30 pv h2e2 h9g7",
+            "info depth 5 multipv 2 score cp 10 pv b2e2",
+            "info depth 6 multipv 1 score cp 25 pv h2e2 b9c7",
+            "info depth 6 currmove b0c2 currmovenumber 3",
+            "info nodes 5000 nps 100000 time 50",
+        ] {
+            progress.update(SearchInfo::parse(line).unwrap());
+        }
+        assert_eq!(progress.lines.len(), 2);
+        assert_eq!(progress.lines[&1].score, Some(Score::Centipawns(25)));
+        assert_eq!(progress.lines[&1].pv, vec!["h2e2", "b9c7"]);
+        assert_eq!(progress.lines[&2].pv, vec!["b2e2"]);
+        assert_eq!(progress.stats.depth, Some(6));
+        assert_eq!(progress.stats.nodes, Some(5000));
+        assert_eq!(progress.stats.score, None);
+    }
+}
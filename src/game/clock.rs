@@ -0,0 +1,292 @@
+//! Game clocks.
+//!
+//! Each side has a main time. Under Fischer rules an increment is added after every move;
+//! under byo-yomi rules, once the main time runs out the side plays in periods, each of which
+//! must hold a number of moves. Running out of a period uses up the next one, and running out
+//! of the last one loses on time.
+
+use crate::game::piece::PieceSide;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// How time is counted.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ClockKind {
+    /// No clocks.
+    #[default]
+    Off,
+    /// The main time is all there is.
+    SuddenDeath,
+    /// An increment is added after every move.
+    Fischer,
+    /// Periods follow the main time.
+    ByoYomi,
+}
+
+impl ClockKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ClockKind::Off => "不计时",
+            ClockKind::SuddenDeath => "包干",
+            ClockKind::Fischer => "加秒",
+            ClockKind::ByoYomi => "读秒",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ClockKind::Off => ClockKind::SuddenDeath,
+            ClockKind::SuddenDeath => ClockKind::Fischer,
+            ClockKind::Fischer => ClockKind::ByoYomi,
+            ClockKind::ByoYomi => ClockKind::Off,
+        }
+    }
+}
+
+/// A time control. Only the fields used by `kind` matter.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub kind: ClockKind,
+    /// Main time per side, in seconds.
+    pub base_secs: u32,
+    /// Seconds added after every move under Fischer rules.
+    pub increment_secs: u32,
+    /// Length of a byo-yomi period, in seconds.
+    pub period_secs: u32,
+    /// Moves to be played in each byo-yomi period.
+    pub period_moves: u32,
+    /// Number of byo-yomi periods.
+    pub periods: u32,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        Self {
+            kind: ClockKind::Off,
+            base_secs: 600,
+            increment_secs: 5,
+            period_secs: 30,
+            period_moves: 1,
+            periods: 3,
+        }
+    }
+}
+
+impl TimeControl {
+    pub fn is_enabled(&self) -> bool {
+        self.kind != ClockKind::Off
+    }
+
+    /// Returns the time added after every move.
+    pub fn increment(&self) -> Duration {
+        match self.kind {
+            ClockKind::Fischer => Duration::from_secs(self.increment_secs.into()),
+            _ => Duration::ZERO,
+        }
+    }
+
+    fn period(&self) -> Duration {
+        Duration::from_secs(self.period_secs.into())
+    }
+}
+
+/// The time one side has left.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SideClock {
+    pub main: Duration,
+    /// Time left in the current byo-yomi period.
+    pub period: Duration,
+    /// Byo-yomi periods left, including the current one.
+    pub periods_left: u32,
+    /// Moves still to be played in the current byo-yomi period.
+    pub moves_left: u32,
+    pub flagged: bool,
+}
+
+impl SideClock {
+    fn new(control: &TimeControl) -> Self {
+        let byo_yomi = control.kind == ClockKind::ByoYomi;
+        Self {
+            main: Duration::from_secs(control.base_secs.into()),
+            period: if byo_yomi {
+                control.period()
+            } else {
+                Duration::ZERO
+            },
+            periods_left: if byo_yomi { control.periods } else { 0 },
+            moves_left: control.period_moves.max(1),
+            flagged: false,
+        }
+    }
+
+    /// Returns true once the main time is used up and the side plays in periods.
+    pub fn in_byo_yomi(&self) -> bool {
+        self.main.is_zero() && self.periods_left > 0
+    }
+
+    /// Returns the time that can be used before the next move is due.
+    pub fn available(&self) -> Duration {
+        if self.in_byo_yomi() {
+            self.period
+        } else {
+            self.main
+        }
+    }
+}
+
+/// The clocks of both sides.
+#[derive(Clone, Debug)]
+pub struct GameClock {
+    pub control: TimeControl,
+    pub red: SideClock,
+    pub black: SideClock,
+    /// When the time since the last tick started counting, or None while paused.
+    ticked_at: Option<Instant>,
+}
+
+impl GameClock {
+    pub fn new(control: TimeControl) -> Self {
+        Self {
+            control,
+            red: SideClock::new(&control),
+            black: SideClock::new(&control),
+            ticked_at: None,
+        }
+    }
+
+    pub fn side(&self, side: PieceSide) -> &SideClock {
+        match side {
+            PieceSide::Red => &self.red,
+            PieceSide::Black => &self.black,
+        }
+    }
+
+    fn side_mut(&mut self, side: PieceSide) -> &mut SideClock {
+        match side {
+            PieceSide::Red => &mut self.red,
+            PieceSide::Black => &mut self.black,
+        }
+    }
+
+    /// Returns the side whose time ran out, if any.
+    pub fn flagged(&self) -> Option<PieceSide> {
+        [PieceSide::Red, PieceSide::Black]
+            .into_iter()
+            .find(|&side| self.side(side).flagged)
+    }
+
+    /// Charges the time since the last tick to the side to move. Returns true if its flag
+    /// fell.
+    pub fn tick(&mut self, side_to_move: PieceSide, now: Instant) -> bool {
+        let elapsed = self
+            .ticked_at
+            .map_or(Duration::ZERO, |then| now.saturating_duration_since(then));
+        self.ticked_at = Some(now);
+        self.spend(side_to_move, elapsed)
+    }
+
+    /// Stops counting until the next tick.
+    pub fn pause(&mut self) {
+        self.ticked_at = None;
+    }
+
+    /// Takes time off a side's clock. Returns true if its flag fell.
+    pub fn spend(&mut self, side: PieceSide, elapsed: Duration) -> bool {
+        let control = self.control;
+        let clock = self.side_mut(side);
+        if clock.flagged {
+            return false;
+        }
+
+        let mut left = elapsed;
+        let used = left.min(clock.main);
+        clock.main -= used;
+        left -= used;
+        while !left.is_zero() || clock.main.is_zero() && clock.periods_left == 0 {
+            if clock.periods_left == 0 {
+                clock.flagged = true;
+                return true;
+            }
+            if left < clock.period {
+                clock.period -= left;
+                break;
+            }
+            left -= clock.period;
+            clock.periods_left -= 1;
+            clock.period = control.period();
+            clock.moves_left = control.period_moves.max(1);
+        }
+        false
+    }
+
+    /// Accounts for a move just completed by `side`.
+    pub fn complete_move(&mut self, side: PieceSide) {
+        let control = self.control;
+        let clock = self.side_mut(side);
+        if clock.flagged {
+            return;
+        }
+        if clock.in_byo_yomi() {
+            clock.moves_left = clock.moves_left.saturating_sub(1);
+            if clock.moves_left == 0 {
+                clock.period = control.period();
+                clock.moves_left = control.period_moves.max(1);
+            }
+        } else {
+            clock.main += control.increment();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn clocks_follow_their_time_control() {
+        let mut control = TimeControl {
+            kind: ClockKind::SuddenDeath,
+            base_secs: 60,
+            ..TimeControl::default()
+        };
+        let mut clock = GameClock::new(control);
+        assert!(!clock.spend(PieceSide::Red, secs(59)));
+        clock.complete_move(PieceSide::Red);
+        assert_eq!(clock.red.main, secs(1));
+        assert!(clock.spend(PieceSide::Red, secs(2)));
+        assert_eq!(clock.flagged(), Some(PieceSide::Red));
+
+        control.kind = ClockKind::Fischer;
+        control.increment_secs = 10;
+        let mut clock = GameClock::new(control);
+        assert!(!clock.spend(PieceSide::Black, secs(30)));
+        clock.complete_move(PieceSide::Black);
+        assert_eq!(clock.black.main, secs(40));
+        assert_eq!(clock.red.main, secs(60));
+
+        control.kind = ClockKind::ByoYomi;
+        control.period_secs = 30;
+        control.period_moves = 2;
+        control.periods = 2;
+        let mut clock = GameClock::new(control);
+        assert!(!clock.spend(PieceSide::Red, secs(70)));
+        assert!(clock.red.in_byo_yomi());
+        assert_eq!(clock.red.period, secs(20));
+        clock.complete_move(PieceSide::Red);
+        assert_eq!(clock.red.moves_left, 1);
+        assert_eq!(clock.red.period, secs(20));
+        clock.complete_move(PieceSide::Red);
+        assert_eq!(clock.red.period, secs(30));
+        assert_eq!(clock.red.moves_left, 2);
+
+        assert!(!clock.spend(PieceSide::Red, secs(35)));
+        assert_eq!(clock.red.periods_left, 1);
+        assert_eq!(clock.red.period, secs(25));
+        assert!(clock.spend(PieceSide::Red, secs(25)));
+        assert!(clock.red.flagged);
+    }
+}
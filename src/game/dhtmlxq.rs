@@ -0,0 +1,508 @@
+//! DhtmlXQ, the UBB-style game format embedded in forum posts and web pages.
+//!
+//! A game is a block of `[DhtmlXQ_name]value[/DhtmlXQ_name]` fields inside
+//! `[DhtmlXQ]...[/DhtmlXQ]`:
+//!
+//! - `binit` lists the squares of the 32 pieces as two digits each, in the same slot order
+//!   as XQF. A square is `xy`, with x the file from Red's left (0-8) and y the rank from
+//!   Black's side (0-9); `99` marks a captured piece. A missing `binit` means the standard
+//!   opening.
+//! - `movelist` is the main line, four digits (from, to) per move.
+//! - `move_A_B_C` is branch C, which replaces move B of branch A (the main line is branch 0).
+//! - `commentN` is the comment on move N of the main line, `comment0` the one before the
+//!   first move; `commentC_N` is the comment on move N of branch C.
+//! - `title`, `event`, `date`, `place`, `red`, `black` and `result` describe the game.
+
+use crate::game::{
+    board::{self, BoardState},
+    fen,
+    notation::NotationError,
+    pgn::{PgnError, PgnGame, PgnMove},
+    piece::{Piece, PieceSide},
+    state::{GameState, GameStatus},
+    tree::{GameTree, NodeId, ROOT},
+    xqf::PIECE_SLOTS,
+};
+use std::collections::BTreeMap;
+
+/// Errors that can occur when reading a DhtmlXQ block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DhtmlXqError {
+    NotDhtmlXq,
+    Malformed(String),
+    InvalidPosition(String),
+    /// A move that cannot be played, with its one-based move number in its branch.
+    IllegalMove {
+        ply: usize,
+        error: NotationError,
+    },
+}
+
+impl std::fmt::Display for DhtmlXqError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DhtmlXqError::NotDhtmlXq => write!(f, "No DhtmlXQ block found"),
+            DhtmlXqError::Malformed(e) => write!(f, "Malformed DhtmlXQ field: {}", e),
+            DhtmlXqError::InvalidPosition(e) => write!(f, "Invalid start position: {}", e),
+            DhtmlXqError::IllegalMove { ply, error } => write!(f, "Move {}: {}", ply, error),
+        }
+    }
+}
+
+impl std::error::Error for DhtmlXqError {}
+
+/// Returns true if the text contains a DhtmlXQ block.
+pub fn is_dhtmlxq(text: &str) -> bool {
+    text.contains("[DhtmlXQ")
+}
+
+/// Collects the `[DhtmlXQ_name]value[/DhtmlXQ_name]` fields of a text.
+fn fields(text: &str) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[DhtmlXQ_") {
+        rest = &rest[start + "[DhtmlXQ_".len()..];
+        let Some(end) = rest.find(']') else {
+            break;
+        };
+        let name = &rest[..end];
+        rest = &rest[end + 1..];
+        let close = format!("[/DhtmlXQ_{}]", name);
+        if let Some(value_end) = rest.find(&close) {
+            fields.insert(name.to_string(), rest[..value_end].trim().to_string());
+            rest = &rest[value_end + close.len()..];
+        }
+    }
+    fields
+}
+
+/// Converts a two-digit DhtmlXQ square to an ICCS square.
+fn square_to_iccs(square: &str) -> Option<String> {
+    let mut digits = square.chars().map(|c| c.to_digit(10));
+    let (x, y) = (digits.next()??, digits.next()??);
+    (x < 9).then(|| format!("{}{}", (b'a' + x as u8) as char, 9 - y))
+}
+
+/// Converts an ICCS square to a two-digit DhtmlXQ square.
+fn iccs_to_square(iccs: &str) -> String {
+    let pos = GameState::uci_to_pos(iccs).expect("ICCS square from a board position");
+    format!("{}{}", pos.1, pos.0)
+}
+
+/// Parses a move list into moves numbered from `first_ply`.
+fn parse_moves(list: &str, first_ply: usize) -> Result<Vec<PgnMove>, DhtmlXqError> {
+    let digits: Vec<char> = list.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(4) || !list.is_ascii() {
+        return Err(DhtmlXqError::Malformed(list.to_string()));
+    }
+    digits
+        .chunks(4)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let chunk: String = chunk.iter().collect();
+            let malformed = || DhtmlXqError::Malformed(chunk.clone());
+            let from = square_to_iccs(&chunk[..2]).ok_or_else(malformed)?;
+            let to = square_to_iccs(&chunk[2..]).ok_or_else(malformed)?;
+            Ok(PgnMove {
+                text: from + &to,
+                line: first_ply + i,
+                comment: None,
+                variations: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+/// A line of moves and where it branches off its parent.
+struct Branch {
+    parent: usize,
+    first_ply: usize,
+    moves: Vec<PgnMove>,
+}
+
+/// Builds a branch with its sub-branches attached as variations.
+fn build_line(
+    branches: &mut BTreeMap<usize, Branch>,
+    index: usize,
+) -> Result<Vec<PgnMove>, DhtmlXqError> {
+    let Some(branch) = branches.remove(&index) else {
+        return Ok(Vec::new());
+    };
+    let mut moves = branch.moves;
+    let children: Vec<usize> = branches
+        .iter()
+        .filter(|(_, b)| b.parent == index)
+        .map(|(&i, _)| i)
+        .collect();
+    for child in children {
+        let first_ply = branches[&child].first_ply;
+        let line = build_line(branches, child)?;
+        let slot = first_ply
+            .checked_sub(branch.first_ply)
+            .and_then(|i| moves.get_mut(i))
+            .ok_or_else(|| {
+                DhtmlXqError::Malformed(format!("move_{}_{}_{}", index, first_ply, child))
+            })?;
+        if !line.is_empty() {
+            slot.variations.push(line);
+        }
+    }
+    Ok(moves)
+}
+
+/// Parses a DhtmlXQ block into a game tree with ICCS moves.
+pub fn parse_dhtmlxq(text: &str) -> Result<PgnGame, DhtmlXqError> {
+    if !is_dhtmlxq(text) {
+        return Err(DhtmlXqError::NotDhtmlXq);
+    }
+    let fields = fields(text);
+
+    let board = match fields.get("binit") {
+        Some(binit) if !binit.is_empty() => {
+            let squares: Vec<char> = binit.chars().filter(|c| !c.is_whitespace()).collect();
+            if squares.len() != 64 {
+                return Err(DhtmlXqError::Malformed(binit.clone()));
+            }
+            let mut board: BoardState = [[None; 9]; 10];
+            for (i, square) in squares.chunks(2).enumerate() {
+                let square: String = square.iter().collect();
+                let Some(iccs) = square_to_iccs(&square) else {
+                    continue;
+                };
+                let (row, col) = GameState::uci_to_pos(&iccs)
+                    .ok_or_else(|| DhtmlXqError::Malformed(binit.clone()))?;
+                if board[row][col].is_some() {
+                    return Err(DhtmlXqError::InvalidPosition(format!(
+                        "two pieces on {}",
+                        iccs
+                    )));
+                }
+                board[row][col] = Piece::from_fen_char(PIECE_SLOTS[i] as char);
+            }
+            Some(board)
+        }
+        _ => None,
+    };
+
+    let mut branches = BTreeMap::new();
+    branches.insert(
+        0,
+        Branch {
+            parent: 0,
+            first_ply: 1,
+            moves: parse_moves(fields.get("movelist").map_or("", |s| s.as_str()), 1)?,
+        },
+    );
+    let mut comments = Vec::new();
+    for (name, value) in &fields {
+        if let Some(spec) = name.strip_prefix("move_") {
+            let numbers: Vec<usize> = spec.split('_').filter_map(|n| n.parse().ok()).collect();
+            let [parent, first_ply, index] = numbers[..] else {
+                return Err(DhtmlXqError::Malformed(name.clone()));
+            };
+            // Branches only refer to earlier ones, which also rules out cycles.
+            if index == 0 || parent >= index {
+                return Err(DhtmlXqError::Malformed(name.clone()));
+            }
+            let moves = parse_moves(value, first_ply)?;
+            branches.insert(
+                index,
+                Branch {
+                    parent,
+                    first_ply,
+                    moves,
+                },
+            );
+        } else if let Some(spec) = name.strip_prefix("comment") {
+            let numbers: Vec<usize> = spec.split('_').filter_map(|n| n.parse().ok()).collect();
+            match numbers[..] {
+                [ply] => comments.push((0, ply, value.clone())),
+                [branch, ply] => comments.push((branch, ply, value.clone())),
+                _ => {}
+            }
+        }
+    }
+
+    let mut initial_comment = None;
+    for (index, ply, comment) in comments {
+        if comment.is_empty() {
+            continue;
+        }
+        if index == 0 && ply == 0 {
+            initial_comment = Some(comment);
+        } else if let Some(branch) = branches.get_mut(&index)
+            && let Some(mv) = ply
+                .checked_sub(branch.first_ply)
+                .and_then(|i| branch.moves.get_mut(i))
+        {
+            mv.comment = Some(comment);
+        }
+    }
+
+    let moves = build_line(&mut branches, 0)?;
+
+    let tag = |name: &str| fields.get(name).cloned().unwrap_or_default();
+    let result = match tag("result").as_str() {
+        "红胜" | "红先胜" | "1-0" => "1-0",
+        "黑胜" | "红先负" | "0-1" => "0-1",
+        "和棋" | "和局" | "红先和" | "1/2-1/2" => "1/2-1/2",
+        _ => "*",
+    }
+    .to_string();
+
+    let mut tags = vec![
+        ("Title".to_string(), tag("title")),
+        ("Event".to_string(), tag("event")),
+        ("Date".to_string(), tag("date")),
+        ("Site".to_string(), tag("place")),
+        ("Red".to_string(), tag("red")),
+        ("Black".to_string(), tag("black")),
+        ("Result".to_string(), result.clone()),
+    ];
+    if let Some(board) = board {
+        let side = moves
+            .first()
+            .and_then(|mv| GameState::uci_to_move(&mv.text))
+            .and_then(|m| board[m.from.0][m.from.1])
+            .map_or(PieceSide::Red, |piece| piece.side);
+        tags.push(("FEN".to_string(), fen::to_fen(&board, side, 0, 1)));
+    }
+    tags.push(("Format".to_string(), "ICCS".to_string()));
+
+    Ok(PgnGame {
+        tags,
+        comment: initial_comment,
+        moves,
+        result,
+    })
+}
+
+/// Reads a DhtmlXQ block into a game, checking every move and variation.
+pub fn load_dhtmlxq(text: &str) -> Result<GameState, DhtmlXqError> {
+    parse_dhtmlxq(text)?.to_game().map_err(|e| match e {
+        PgnError::IllegalMove { line, error } => DhtmlXqError::IllegalMove { ply: line, error },
+        other => DhtmlXqError::InvalidPosition(other.to_string()),
+    })
+}
+
+/// Formats the `binit` field for a board, leaving pieces that fit no slot out.
+fn binit(board: &BoardState) -> String {
+    let mut remaining: Vec<(usize, usize)> = (0..10)
+        .flat_map(|row| (0..9).map(move |col| (row, col)))
+        .filter(|&(row, col)| board[row][col].is_some())
+        .collect();
+    let mut out = String::new();
+    for &slot in PIECE_SLOTS {
+        let piece = Piece::from_fen_char(slot as char);
+        match remaining
+            .iter()
+            .position(|&(row, col)| board[row][col] == piece)
+        {
+            Some(i) => {
+                let pos = remaining.remove(i);
+                out.push_str(&iccs_to_square(&GameState::pos_to_uci(pos)));
+            }
+            None => out.push_str("99"),
+        }
+    }
+    out
+}
+
+/// Writes the line starting at `first` as branch `branch`, whose first move is ply
+/// `first_ply`. Comments and sub-branches are added to `fields`; `last_branch` is the
+/// highest branch number used so far.
+fn export_branch(
+    tree: &GameTree,
+    first: NodeId,
+    branch: usize,
+    first_ply: usize,
+    fields: &mut Vec<(String, String)>,
+    last_branch: &mut usize,
+) -> String {
+    let mut moves = String::new();
+    let mut ply = first_ply;
+    let mut next = Some(first);
+    while let Some(id) = next {
+        let node = tree.node(id);
+        let Some(m) = node.mv else {
+            break;
+        };
+        let uci = GameState::move_to_uci(&m);
+        moves.push_str(&(iccs_to_square(&uci[..2]) + &iccs_to_square(&uci[2..])));
+        if let Some(comment) = &node.comment {
+            let name = if branch == 0 {
+                format!("comment{}", ply)
+            } else {
+                format!("comment{}_{}", branch, ply)
+            };
+            fields.push((name, comment.clone()));
+        }
+        if tree.variation_index(id) == 0
+            && let Some(parent) = node.parent
+        {
+            for &variation in &tree.node(parent).children[1..] {
+                *last_branch += 1;
+                let index = *last_branch;
+                let line = export_branch(tree, variation, index, ply, fields, last_branch);
+                fields.push((format!("move_{}_{}_{}", branch, ply, index), line));
+            }
+        }
+        ply += 1;
+        next = node.children.first().copied();
+    }
+    moves
+}
+
+/// Keeps a value from opening or closing a field by writing the bracket of any tag in it as
+/// the full-width `［`.
+fn field_value(value: &str) -> String {
+    value
+        .replace("[DhtmlXQ", "［DhtmlXQ")
+        .replace("[/DhtmlXQ", "［/DhtmlXQ")
+}
+
+/// Formats a game, with its variations and comments, as a DhtmlXQ block.
+pub fn to_dhtmlxq(game: &GameState) -> String {
+    let start = match &game.start_fen {
+        Some(fen) => fen::parse_placement(fen.split_whitespace().next().unwrap_or_default())
+            .unwrap_or_else(|_| board::init_board()),
+        None => board::init_board(),
+    };
+    let mut branches = Vec::new();
+    if let Some(comment) = &game.tree.node(ROOT).comment {
+        branches.push(("comment0".to_string(), comment.clone()));
+    }
+    let movelist = match game.tree.node(ROOT).children.first() {
+        Some(&first) => export_branch(&game.tree, first, 0, 1, &mut branches, &mut 0),
+        None => String::new(),
+    };
+    let result = match game.final_status() {
+        GameStatus::InProgress => "未知",
+        GameStatus::RedWins(_) => "红胜",
+        GameStatus::BlackWins(_) => "黑胜",
+        GameStatus::Draw(_) => "和棋",
+    };
+
+    let metadata = &game.metadata;
+    let fields = [
+        ("ver", "www_dhtmlxq_com"),
+        ("event", metadata.event.as_str()),
+        ("date", metadata.date.as_str()),
+        ("place", metadata.site.as_str()),
+        ("red", metadata.red.as_str()),
+        ("black", metadata.black.as_str()),
+        ("result", result),
+        ("binit", &binit(&start)),
+        ("movelist", &movelist),
+    ];
+
+    let mut out = String::from("[DhtmlXQ]\n");
+    for (name, value) in fields {
+        out.push_str(&format!(
+            "[DhtmlXQ_{0}]{1}[/DhtmlXQ_{0}]\n",
+            name,
+            field_value(value)
+        ));
+    }
+    for (name, value) in branches {
+        out.push_str(&format!(
+            "[DhtmlXQ_{0}]{1}[/DhtmlXQ_{0}]\n",
+            name,
+            field_value(&value)
+        ));
+    }
+    out.push_str("[/DhtmlXQ]\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "[DhtmlXQ]
+[DhtmlXQ_ver]www_dhtmlxq_com[/DhtmlXQ_ver]
+[DhtmlXQ_red]甲[/DhtmlXQ_red]
+[DhtmlXQ_black]乙[/DhtmlXQ_black]
+[DhtmlXQ_result]红胜[/DhtmlXQ_result]
+[DhtmlXQ_binit]8979695949392919097717866646260600102030405060708012720323436383[/DhtmlXQ_binit]
+[DhtmlXQ_movelist]774770627967[/DhtmlXQ_movelist]
+[DhtmlXQ_move_0_2_1]10227967[/DhtmlXQ_move_0_2_1]
+[DhtmlXQ_move_1_3_2]1927[/DhtmlXQ_move_1_3_2]
+[DhtmlXQ_comment0]开局[/DhtmlXQ_comment0]
+[DhtmlXQ_comment2]屏风马[/DhtmlXQ_comment2]
+[DhtmlXQ_comment1_2]单提马[/DhtmlXQ_comment1_2]
+[/DhtmlXQ]";
+
+    #[test]
+    fn parses_branches_and_comments() {
+        let pgn = parse_dhtmlxq(SAMPLE).unwrap();
+        let texts: Vec<&str> = pgn.moves.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(texts, ["h2e2", "h9g7", "h0g2"]);
+        assert_eq!(pgn.comment.as_deref(), Some("开局"));
+        assert_eq!(pgn.moves[1].comment.as_deref(), Some("屏风马"));
+        assert_eq!(pgn.result, "1-0");
+
+        let branch = &pgn.moves[1].variations[0];
+        assert_eq!(branch[0].text, "b9c7");
+        assert_eq!(branch[0].comment.as_deref(), Some("单提马"));
+        assert_eq!(branch.len(), 2);
+        assert_eq!(branch[1].variations[0][0].text, "b0c2");
+
+        let mut game = load_dhtmlxq(SAMPLE).unwrap();
+        assert_eq!(game.moves_to_uci(), "h2e2 h9g7 h0g2");
+        assert_eq!(game.metadata.red, "甲");
+
+        let written = parse_dhtmlxq(&to_dhtmlxq(&game)).unwrap();
+        assert_eq!(written.comment, pgn.comment);
+        assert_eq!(written.moves, pgn.moves);
+        assert_eq!(written.result, "1-0");
+
+        // Written while browsing the start, the block still holds the result.
+        game.go_to(ROOT);
+        assert_eq!(parse_dhtmlxq(&to_dhtmlxq(&game)).unwrap().result, "1-0");
+    }
+
+    #[test]
+    fn written_blocks_read_back() {
+        let mut game = GameState::from_fen("4k4/9/9/9/9/9/9/9/4A4/3K1R3 w - - 0 1").unwrap();
+        for uci in ["f0f9", "e9e8", "f9f8"] {
+            let m = GameState::uci_to_move(uci).unwrap();
+            game.make_move(m.from, m.to);
+        }
+        let text = to_dhtmlxq(&game);
+        let loaded = load_dhtmlxq(&text).unwrap();
+        assert_eq!(loaded.moves_to_uci(), game.moves_to_uci());
+        assert_eq!(loaded.board, game.board);
+        assert_eq!(loaded.start_fen, game.start_fen);
+    }
+
+    #[test]
+    fn tags_in_comments_are_neutralized() {
+        let mut game = load_dhtmlxq(SAMPLE).unwrap();
+        let first = game.tree.node(ROOT).children[0];
+        game.tree.set_comment(
+            first,
+            Some("[/DhtmlXQ_comment1][DhtmlXQ_movelist]1927[/DhtmlXQ_movelist]".to_string()),
+        );
+        game.metadata.red = "甲[/DhtmlXQ]".to_string();
+
+        let loaded = load_dhtmlxq(&to_dhtmlxq(&game)).unwrap();
+        assert_eq!(loaded.moves_to_uci(), game.moves_to_uci());
+        assert_eq!(loaded.metadata.red, "甲［/DhtmlXQ]");
+        let first = loaded.tree.node(ROOT).children[0];
+        assert_eq!(
+            loaded.tree.node(first).comment.as_deref(),
+            Some("［/DhtmlXQ_comment1]［DhtmlXQ_movelist]1927［/DhtmlXQ_movelist]")
+        );
+    }
+
+    #[test]
+    fn rejects_illegal_moves() {
+        let text = "[DhtmlXQ_movelist]77477747[/DhtmlXQ_movelist]";
+        assert!(matches!(
+            load_dhtmlxq(text),
+            Err(DhtmlXqError::IllegalMove { ply: 2, .. })
+        ));
+        assert_eq!(parse_dhtmlxq("hello"), Err(DhtmlXqError::NotDhtmlXq));
+    }
+}
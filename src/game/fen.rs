@@ -0,0 +1,215 @@
+use crate::game::{
+    board::BoardState,
+    piece::{Piece, PieceSide},
+    rules::PositionProblem,
+};
+
+/// Errors that can occur when parsing a FEN string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FenError {
+    Empty,
+    RankCount(usize),
+    RankLength {
+        rank: usize,
+        length: usize,
+    },
+    InvalidPiece(char),
+    InvalidSide(String),
+    InvalidCounter(String),
+    /// The placement parses but cannot occur in a game.
+    IllegalPosition(Vec<PositionProblem>),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::Empty => write!(f, "FEN string is empty"),
+            FenError::RankCount(n) => write!(f, "Expected 10 ranks, found {}", n),
+            FenError::RankLength { rank, length } => {
+                write!(f, "Rank {} has {} files, expected 9", rank + 1, length)
+            }
+            FenError::InvalidPiece(c) => write!(f, "Invalid piece character '{}'", c),
+            FenError::InvalidSide(s) => write!(f, "Invalid side to move '{}'", s),
+            FenError::InvalidCounter(s) => write!(f, "Invalid move counter '{}'", s),
+            FenError::IllegalPosition(problems) => {
+                let problems: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+                write!(f, "Illegal position: {}", problems.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// The fields of a parsed FEN string.
+pub struct Fen {
+    pub board: BoardState,
+    pub side: PieceSide,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+}
+
+/// Parses the piece placement field, rank 9 (Black's back rank) first.
+pub fn parse_placement(placement: &str) -> Result<BoardState, FenError> {
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 10 {
+        return Err(FenError::RankCount(ranks.len()));
+    }
+
+    let mut board: BoardState = [[None; 9]; 10];
+    for (row, rank) in ranks.iter().enumerate() {
+        let mut col = 0;
+        for c in rank.chars() {
+            if let Some(empty) = c.to_digit(10) {
+                col += empty as usize;
+            } else {
+                let piece = Piece::from_fen_char(c).ok_or(FenError::InvalidPiece(c))?;
+                if col < 9 {
+                    board[row][col] = Some(piece);
+                }
+                col += 1;
+            }
+        }
+        if col != 9 {
+            return Err(FenError::RankLength {
+                rank: row,
+                length: col,
+            });
+        }
+    }
+
+    Ok(board)
+}
+
+/// Formats the piece placement field of a board.
+pub fn placement_to_fen(board: &BoardState) -> String {
+    let mut out = String::new();
+    for (row, rank) in board.iter().enumerate() {
+        if row > 0 {
+            out.push('/');
+        }
+        let mut empty = 0;
+        for square in rank {
+            match square {
+                Some(piece) => {
+                    if empty > 0 {
+                        out.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    out.push(piece.fen_char());
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            out.push_str(&empty.to_string());
+        }
+    }
+    out
+}
+
+/// Parses a full FEN string.
+///
+/// Accepts both `w` and `r` for Red to move, and tolerates the unused
+/// castling/en-passant placeholders (`- -`) and missing move counters.
+pub fn parse_fen(fen: &str) -> Result<Fen, FenError> {
+    let mut fields = fen.split_whitespace();
+    let board = parse_placement(fields.next().ok_or(FenError::Empty)?)?;
+
+    let side = match fields.next() {
+        None | Some("w") | Some("r") => PieceSide::Red,
+        Some("b") => PieceSide::Black,
+        Some(other) => return Err(FenError::InvalidSide(other.to_string())),
+    };
+
+    let counters: Vec<&str> = fields.filter(|f| *f != "-").collect();
+    let parse_counter = |s: &str| {
+        s.parse::<u32>()
+            .map_err(|_| FenError::InvalidCounter(s.to_string()))
+    };
+    let halfmove_clock = counters.first().map_or(Ok(0), |s| parse_counter(s))?;
+    let fullmove_number = counters.get(1).map_or(Ok(1), |s| parse_counter(s))?.max(1);
+
+    Ok(Fen {
+        board,
+        side,
+        halfmove_clock,
+        fullmove_number,
+    })
+}
+
+/// Formats a full FEN string in the form emitted by Pikafish.
+pub fn to_fen(
+    board: &BoardState,
+    side: PieceSide,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+) -> String {
+    let side = match side {
+        PieceSide::Red => 'w',
+        PieceSide::Black => 'b',
+    };
+    format!(
+        "{} {} - - {} {}",
+        placement_to_fen(board),
+        side,
+        halfmove_clock,
+        fullmove_number
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{rules::PositionProblem, state::GameState};
+
+    const START: &str = "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1";
+
+    #[test]
+    fn round_trips_pikafish_fen() {
+        let game = GameState::default();
+        assert_eq!(game.to_fen(), START);
+        assert_eq!(GameState::from_fen(START).unwrap().to_fen(), START);
+
+        let mut game = GameState::default();
+        for uci in ["h2e2", "h9g7", "h0g2", "i9h9"] {
+            let m = GameState::uci_to_move(uci).unwrap();
+            game.make_move(m.from, m.to);
+        }
+        let midgame = "rnbakabr1/9/1c4nc1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C1N2/9/RNBAKAB1R w - - 4 3";
+        assert_eq!(game.to_fen(), midgame);
+        let parsed = GameState::from_fen(midgame).unwrap();
+        assert_eq!(parsed.board, game.board);
+        assert_eq!(parsed.current_turn, PieceSide::Red);
+        assert_eq!(parsed.to_fen(), midgame);
+    }
+
+    #[test]
+    fn reports_each_kind_of_error() {
+        let err = |fen: &str| parse_fen(fen).err();
+        assert_eq!(err("  "), Some(FenError::Empty));
+        assert_eq!(err("9/9/9"), Some(FenError::RankCount(3)));
+        assert_eq!(
+            err("rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABN"),
+            Some(FenError::RankLength { rank: 9, length: 8 })
+        );
+        assert_eq!(
+            err("rnbakabnx/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR"),
+            Some(FenError::InvalidPiece('x'))
+        );
+        let board = START.split(' ').next().unwrap();
+        assert_eq!(
+            err(&format!("{} x", board)),
+            Some(FenError::InvalidSide("x".to_string()))
+        );
+        assert_eq!(
+            err(&format!("{} w - - 0 one", board)),
+            Some(FenError::InvalidCounter("one".to_string()))
+        );
+        assert!(matches!(
+            GameState::from_fen("9/9/9/9/9/9/9/9/9/4K4 w - - 0 1").err(),
+            Some(FenError::IllegalPosition(problems))
+                if problems.contains(&PositionProblem::MissingGeneral(PieceSide::Black))
+        ));
+    }
+}
@@ -1,4 +1,13 @@
 pub mod board;
+pub mod clock;
+pub mod dhtmlxq;
+pub mod fen;
+pub mod notation;
+pub mod pgn;
 pub mod piece;
+pub mod record;
 pub mod rules;
 pub mod state;
+pub mod tree;
+pub mod xqf;
+pub mod zobrist;
@@ -0,0 +1,805 @@
+//! Descriptive move notations: Chinese (炮二平五) and WXF (C2.5).
+//!
+//! Both name a move by piece, file and direction, so they share [`describe`] and
+//! [`resolve`]. ICCS coordinates live with the other UCI helpers on [`GameState`], which
+//! also offers `parse_move` and `format_move` for every notation.
+
+use crate::game::{
+    board::BoardState,
+    piece::{Piece, PieceKind, PieceSide},
+    rules::{Position, get_valid_moves},
+    state::{GameState, Move},
+};
+use serde::{Deserialize, Serialize};
+
+/// Errors that can occur when resolving a move written in descriptive notation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NotationError {
+    Malformed(String),
+    NoSuchPiece(String),
+    IllegalMove(String),
+    Ambiguous(String),
+}
+
+impl std::fmt::Display for NotationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotationError::Malformed(s) => write!(f, "Cannot parse move '{}'", s),
+            NotationError::NoSuchPiece(s) => write!(f, "No piece matches '{}'", s),
+            NotationError::IllegalMove(s) => write!(f, "Move '{}' is not legal", s),
+            NotationError::Ambiguous(s) => write!(f, "Move '{}' matches several pieces", s),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+/// The notations a move can be written in.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Notation {
+    #[default]
+    Chinese,
+    Wxf,
+    Iccs,
+}
+
+impl Notation {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Notation::Chinese => "中文",
+            Notation::Wxf => "WXF",
+            Notation::Iccs => "ICCS",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Notation::Chinese => Notation::Wxf,
+            Notation::Wxf => Notation::Iccs,
+            Notation::Iccs => Notation::Chinese,
+        }
+    }
+}
+
+/// Formats a move on the board before it is made.
+pub fn format_move(board: &BoardState, m: &Move, notation: Notation) -> Option<String> {
+    match notation {
+        Notation::Chinese => to_chinese(board, m),
+        Notation::Wxf => to_wxf(board, m),
+        Notation::Iccs => Some(GameState::move_to_uci(m)),
+    }
+}
+
+/// Parses a move in any supported notation for `side`.
+///
+/// ICCS coordinates are tried first, then Chinese, then WXF.
+pub fn parse_move(board: &BoardState, side: PieceSide, text: &str) -> Result<Move, NotationError> {
+    let text = text.trim();
+    if !text.is_ascii() {
+        return from_chinese(board, side, text);
+    }
+    if let Some(m) = GameState::uci_to_move(text) {
+        let piece = board[m.from.0][m.from.1].filter(|p| p.side == side);
+        if piece.is_none() {
+            return Err(NotationError::NoSuchPiece(text.into()));
+        }
+        if !get_valid_moves(board, m.from, side).contains(&m.to) {
+            return Err(NotationError::IllegalMove(text.into()));
+        }
+        return Ok(Move {
+            captured: board[m.to.0][m.to.1],
+            ..m
+        });
+    }
+    from_wxf(board, side, text)
+}
+
+/// Direction of a move relative to the moving side.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    Forward,
+    Backward,
+    Traverse,
+}
+
+/// Place of a piece in a file holding several pieces of the same kind.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Place {
+    Front,
+    /// The middle one of three.
+    Middle,
+    Rear,
+    /// Zero-based position from the front, used when a file holds four or five pawns.
+    Nth(usize),
+}
+
+impl Place {
+    fn new(index: usize, count: usize) -> Self {
+        match (count, index) {
+            (2 | 3, 0) => Place::Front,
+            (3, 1) => Place::Middle,
+            (2, 1) | (3, 2) => Place::Rear,
+            _ => Place::Nth(index),
+        }
+    }
+
+    /// Picks the row for this place among rows ordered front to back.
+    fn pick(&self, rows: &[usize]) -> Option<usize> {
+        match self {
+            Place::Front => rows.first().copied(),
+            Place::Middle if rows.len() == 3 => Some(rows[1]),
+            Place::Middle => None,
+            Place::Rear => rows.last().copied(),
+            Place::Nth(i) => rows.get(*i).copied(),
+        }
+    }
+}
+
+/// Identifies which piece moves: by its file, or by its place in a file holding several
+/// pieces of the same kind.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Designator {
+    File(usize),
+    Tandem {
+        place: Place,
+        /// Given when several files hold tandem pawns, in which case it replaces the piece name.
+        file: Option<usize>,
+    },
+}
+
+/// A move in the language shared by traditional Chinese and WXF notation.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Descriptor {
+    pub kind: PieceKind,
+    pub designator: Designator,
+    pub action: Action,
+    /// Steps moved for straight-line moves, otherwise the destination file.
+    pub target: usize,
+}
+
+/// Returns the file number (1-9, counted from the side's right) of a column.
+pub fn file_of(col: usize, side: PieceSide) -> usize {
+    match side {
+        PieceSide::Red => 9 - col,
+        PieceSide::Black => col + 1,
+    }
+}
+
+/// Returns the column of a file number for the given side.
+fn col_of(file: usize, side: PieceSide) -> Option<usize> {
+    if !(1..=9).contains(&file) {
+        return None;
+    }
+    Some(match side {
+        PieceSide::Red => 9 - file,
+        PieceSide::Black => file - 1,
+    })
+}
+
+/// Returns true if the piece moves along lines, so its target is a step count.
+fn moves_in_lines(kind: PieceKind) -> bool {
+    matches!(
+        kind,
+        PieceKind::Jiang | PieceKind::Ju | PieceKind::Pao | PieceKind::Zu
+    )
+}
+
+/// Returns the rows of same-kind pieces in a column, ordered from the side's front.
+fn tandem_rows(board: &BoardState, col: usize, piece: Piece) -> Vec<usize> {
+    let mut rows: Vec<usize> = (0..10).filter(|&r| board[r][col] == Some(piece)).collect();
+    if piece.side == PieceSide::Black {
+        rows.reverse();
+    }
+    rows
+}
+
+/// Returns the columns holding two or more pawns of the side.
+fn tandem_pawn_cols(board: &BoardState, side: PieceSide) -> Vec<usize> {
+    let pawn = Piece {
+        side,
+        kind: PieceKind::Zu,
+    };
+    (0..9)
+        .filter(|&c| tandem_rows(board, c, pawn).len() >= 2)
+        .collect()
+}
+
+/// Describes a move on the board before it is made.
+pub fn describe(board: &BoardState, m: &Move) -> Option<Descriptor> {
+    let piece = board[m.from.0][m.from.1]?;
+    let side = piece.side;
+
+    // Advisors and elephants sharing a file can only move apart, so they keep the file
+    // number (相三退五) instead of being told apart as front and rear.
+    let rows = tandem_rows(board, m.from.1, piece);
+    let tandem = rows.len() >= 2 && !matches!(piece.kind, PieceKind::Shi | PieceKind::Xiang);
+    let designator = if tandem {
+        let index = rows.iter().position(|&r| r == m.from.0)?;
+        let multi_file = piece.kind == PieceKind::Zu && tandem_pawn_cols(board, side).len() >= 2;
+        Designator::Tandem {
+            place: Place::new(index, rows.len()),
+            file: multi_file.then(|| file_of(m.from.1, side)),
+        }
+    } else {
+        Designator::File(file_of(m.from.1, side))
+    };
+
+    let advance = match side {
+        PieceSide::Red => m.from.0 as i32 - m.to.0 as i32,
+        PieceSide::Black => m.to.0 as i32 - m.from.0 as i32,
+    };
+    let action = match advance {
+        0 => Action::Traverse,
+        a if a > 0 => Action::Forward,
+        _ => Action::Backward,
+    };
+    let target = if action != Action::Traverse && moves_in_lines(piece.kind) {
+        advance.unsigned_abs() as usize
+    } else {
+        file_of(m.to.1, side)
+    };
+
+    Some(Descriptor {
+        kind: piece.kind,
+        designator,
+        action,
+        target,
+    })
+}
+
+/// Finds the legal move for `side` matching a descriptor.
+pub fn resolve(
+    board: &BoardState,
+    side: PieceSide,
+    desc: &Descriptor,
+    text: &str,
+) -> Result<Move, NotationError> {
+    let piece = Piece {
+        side,
+        kind: desc.kind,
+    };
+
+    let mut sources: Vec<Position> = Vec::new();
+    match desc.designator {
+        Designator::File(file) => {
+            let col = col_of(file, side).ok_or_else(|| NotationError::Malformed(text.into()))?;
+            sources.extend(tandem_rows(board, col, piece).into_iter().map(|r| (r, col)));
+        }
+        Designator::Tandem { place, file } => {
+            let cols: Vec<usize> = match file {
+                Some(file) => {
+                    vec![col_of(file, side).ok_or_else(|| NotationError::Malformed(text.into()))?]
+                }
+                None => (0..9).collect(),
+            };
+            for col in cols {
+                let rows = tandem_rows(board, col, piece);
+                if rows.len() >= 2
+                    && let Some(row) = place.pick(&rows)
+                {
+                    sources.push((row, col));
+                }
+            }
+        }
+    }
+    if sources.is_empty() {
+        return Err(NotationError::NoSuchPiece(text.into()));
+    }
+
+    let forward: i32 = match side {
+        PieceSide::Red => -1,
+        PieceSide::Black => 1,
+    };
+    let mut candidates = Vec::new();
+    for from in sources {
+        let to = match desc.action {
+            Action::Traverse => col_of(desc.target, side).map(|col| (from.0 as i32, col)),
+            Action::Forward | Action::Backward => {
+                let sign = if desc.action == Action::Forward {
+                    forward
+                } else {
+                    -forward
+                };
+                if moves_in_lines(desc.kind) {
+                    Some((from.0 as i32 + sign * desc.target as i32, from.1))
+                } else {
+                    col_of(desc.target, side).and_then(|col| {
+                        let dcol = (col as i32 - from.1 as i32).abs();
+                        let drow = match desc.kind {
+                            PieceKind::Ma if dcol == 1 || dcol == 2 => 3 - dcol,
+                            PieceKind::Xiang if dcol == 2 => 2,
+                            PieceKind::Shi if dcol == 1 => 1,
+                            _ => return None,
+                        };
+                        Some((from.0 as i32 + sign * drow, col))
+                    })
+                }
+            }
+        };
+        let Some((row, col)) = to else {
+            continue;
+        };
+        if !(0..10).contains(&row) {
+            continue;
+        }
+        let to = (row as usize, col);
+        if get_valid_moves(board, from, side).contains(&to) {
+            candidates.push(Move {
+                from,
+                to,
+                captured: board[to.0][to.1],
+            });
+        }
+    }
+
+    match candidates.len() {
+        0 => Err(NotationError::IllegalMove(text.into())),
+        1 => Ok(candidates[0]),
+        _ => Err(NotationError::Ambiguous(text.into())),
+    }
+}
+
+const CHINESE_DIGITS: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+const FULLWIDTH_DIGITS: [char; 9] = ['１', '２', '３', '４', '５', '６', '７', '８', '９'];
+
+/// Formats a number the way the given side writes it: Chinese numerals for Red, digits for Black.
+fn chinese_number(n: usize, side: PieceSide) -> String {
+    match side {
+        PieceSide::Red => CHINESE_DIGITS
+            .get(n.wrapping_sub(1))
+            .map_or_else(|| n.to_string(), |c| c.to_string()),
+        PieceSide::Black => n.to_string(),
+    }
+}
+
+/// Parses a single-digit number in any of the Chinese, ASCII or full-width forms.
+fn parse_chinese_number(c: char) -> Option<usize> {
+    if let Some(i) = CHINESE_DIGITS.iter().position(|&d| d == c) {
+        return Some(i + 1);
+    }
+    if let Some(i) = FULLWIDTH_DIGITS.iter().position(|&d| d == c) {
+        return Some(i + 1);
+    }
+    c.to_digit(10).map(|d| d as usize).filter(|&d| d > 0)
+}
+
+/// Returns the Chinese prefix naming a piece's place in a file, e.g. 前, 中 or 三.
+fn chinese_place(place: Place) -> char {
+    match place {
+        Place::Front => '前',
+        Place::Middle => '中',
+        Place::Rear => '后',
+        Place::Nth(i) => CHINESE_DIGITS[i],
+    }
+}
+
+fn parse_piece_char(c: char) -> Option<PieceKind> {
+    Some(match c {
+        '帅' | '帥' | '将' | '將' => PieceKind::Jiang,
+        '仕' | '士' => PieceKind::Shi,
+        '相' | '象' => PieceKind::Xiang,
+        '马' | '馬' | '傌' => PieceKind::Ma,
+        '车' | '車' | '俥' => PieceKind::Ju,
+        '炮' | '砲' | '包' => PieceKind::Pao,
+        '兵' | '卒' => PieceKind::Zu,
+        _ => return None,
+    })
+}
+
+/// Formats a move in traditional Chinese notation from the mover's perspective, e.g. 炮二平五.
+pub fn to_chinese(board: &BoardState, m: &Move) -> Option<String> {
+    let piece = board[m.from.0][m.from.1]?;
+    let side = piece.side;
+    let desc = describe(board, m)?;
+
+    let mut out = String::new();
+    match desc.designator {
+        Designator::File(file) => {
+            out.push_str(piece.label());
+            out.push_str(&chinese_number(file, side));
+        }
+        Designator::Tandem { place, file } => {
+            out.push(chinese_place(place));
+            match file {
+                Some(file) => out.push_str(&chinese_number(file, side)),
+                None => out.push_str(piece.label()),
+            }
+        }
+    }
+    out.push(match desc.action {
+        Action::Forward => '进',
+        Action::Backward => '退',
+        Action::Traverse => '平',
+    });
+    out.push_str(&chinese_number(desc.target, side));
+    Some(out)
+}
+
+/// Parses a move in traditional Chinese notation for `side` and resolves it to a legal move.
+///
+/// Accepts traditional character forms (馬, 車, 進, 後) and Chinese, ASCII or full-width digits.
+pub fn from_chinese(
+    board: &BoardState,
+    side: PieceSide,
+    text: &str,
+) -> Result<Move, NotationError> {
+    let malformed = || NotationError::Malformed(text.to_string());
+    let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.len() != 4 {
+        return Err(malformed());
+    }
+
+    let (kind, designator) = if let Some(kind) = parse_piece_char(chars[0]) {
+        let file = parse_chinese_number(chars[1]).ok_or_else(malformed)?;
+        (kind, Designator::File(file))
+    } else {
+        let place = match chars[0] {
+            '前' => Place::Front,
+            '中' => Place::Middle,
+            '后' | '後' => Place::Rear,
+            c => match CHINESE_DIGITS.iter().position(|&d| d == c) {
+                Some(i) if i < 5 => Place::Nth(i),
+                _ => return Err(malformed()),
+            },
+        };
+        match parse_piece_char(chars[1]) {
+            Some(kind) => (kind, Designator::Tandem { place, file: None }),
+            None => {
+                let file = parse_chinese_number(chars[1]).ok_or_else(malformed)?;
+                (
+                    PieceKind::Zu,
+                    Designator::Tandem {
+                        place,
+                        file: Some(file),
+                    },
+                )
+            }
+        }
+    };
+
+    let action = match chars[2] {
+        '进' | '進' => Action::Forward,
+        '退' => Action::Backward,
+        '平' => Action::Traverse,
+        _ => return Err(malformed()),
+    };
+    let target = parse_chinese_number(chars[3]).ok_or_else(malformed)?;
+
+    resolve(
+        board,
+        side,
+        &Descriptor {
+            kind,
+            designator,
+            action,
+            target,
+        },
+        text,
+    )
+}
+
+/// Returns the WXF letter of a piece kind.
+fn wxf_letter(kind: PieceKind) -> char {
+    match kind {
+        PieceKind::Jiang => 'K',
+        PieceKind::Shi => 'A',
+        PieceKind::Xiang => 'E',
+        PieceKind::Ma => 'H',
+        PieceKind::Ju => 'R',
+        PieceKind::Pao => 'C',
+        PieceKind::Zu => 'P',
+    }
+}
+
+/// Parses a WXF piece letter in either case, accepting the B and N aliases.
+fn parse_wxf_letter(c: char) -> Option<PieceKind> {
+    Some(match c.to_ascii_uppercase() {
+        'K' | 'G' => PieceKind::Jiang,
+        'A' => PieceKind::Shi,
+        'E' | 'B' => PieceKind::Xiang,
+        'H' | 'N' => PieceKind::Ma,
+        'R' => PieceKind::Ju,
+        'C' => PieceKind::Pao,
+        'P' => PieceKind::Zu,
+        _ => return None,
+    })
+}
+
+/// Returns the WXF tandem marker: + front, = middle, - rear, and a-e counted from the
+/// front when a file holds four or five pawns.
+fn wxf_place(place: Place) -> char {
+    match place {
+        Place::Front => '+',
+        Place::Middle => '=',
+        Place::Rear => '-',
+        Place::Nth(i) => (b'a' + i as u8) as char,
+    }
+}
+
+fn parse_wxf_place(c: char) -> Option<Place> {
+    Some(match c {
+        '+' => Place::Front,
+        '=' | '.' => Place::Middle,
+        '-' => Place::Rear,
+        'a'..='e' => Place::Nth(c as usize - 'a' as usize),
+        _ => return None,
+    })
+}
+
+/// Formats a move in WXF notation, e.g. C2=5, H8+7 or +R-3.
+pub fn to_wxf(board: &BoardState, m: &Move) -> Option<String> {
+    let desc = describe(board, m)?;
+
+    let mut out = String::new();
+    match desc.designator {
+        Designator::File(file) => {
+            out.push(wxf_letter(desc.kind));
+            out.push_str(&file.to_string());
+        }
+        Designator::Tandem { place, file } => {
+            out.push(wxf_place(place));
+            match file {
+                Some(file) => out.push_str(&file.to_string()),
+                None => out.push(wxf_letter(desc.kind)),
+            }
+        }
+    }
+    out.push(match desc.action {
+        Action::Forward => '+',
+        Action::Backward => '-',
+        Action::Traverse => '=',
+    });
+    out.push_str(&desc.target.to_string());
+    Some(out)
+}
+
+/// Parses a move in WXF notation for `side` and resolves it to a legal move.
+///
+/// Tandem markers may precede the piece letter (+R+1) or follow it (R++1), and `.` is
+/// accepted in place of `=` for sideways moves.
+pub fn from_wxf(board: &BoardState, side: PieceSide, text: &str) -> Result<Move, NotationError> {
+    let malformed = || NotationError::Malformed(text.to_string());
+    let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.len() != 4 {
+        return Err(malformed());
+    }
+
+    let digit = |c: char| c.to_digit(10).map(|d| d as usize).filter(|&d| d > 0);
+    let (kind, designator) = match (chars[0], chars[1]) {
+        (c, f) if parse_wxf_letter(c).is_some() && digit(f).is_some() => (
+            parse_wxf_letter(c).ok_or_else(malformed)?,
+            Designator::File(digit(f).ok_or_else(malformed)?),
+        ),
+        (c, p) if parse_wxf_letter(c).is_some() && matches!(p, '+' | '-' | '=' | '.') => (
+            parse_wxf_letter(c).ok_or_else(malformed)?,
+            Designator::Tandem {
+                place: parse_wxf_place(p).ok_or_else(malformed)?,
+                file: None,
+            },
+        ),
+        (p, c) => {
+            let place = parse_wxf_place(p).ok_or_else(malformed)?;
+            if let Some(kind) = parse_wxf_letter(c) {
+                (kind, Designator::Tandem { place, file: None })
+            } else {
+                (
+                    PieceKind::Zu,
+                    Designator::Tandem {
+                        place,
+                        file: Some(digit(c).ok_or_else(malformed)?),
+                    },
+                )
+            }
+        }
+    };
+
+    let action = match chars[2] {
+        '+' => Action::Forward,
+        '-' => Action::Backward,
+        '=' | '.' => Action::Traverse,
+        _ => return Err(malformed()),
+    };
+    let target = digit(chars[3]).ok_or_else(malformed)?;
+
+    resolve(
+        board,
+        side,
+        &Descriptor {
+            kind,
+            designator,
+            action,
+            target,
+        },
+        text,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{fen::parse_fen, rules::get_all_valid_moves, state::GameState};
+
+    fn board(fen: &str) -> BoardState {
+        parse_fen(fen).unwrap().board
+    }
+
+    fn chinese(fen: &str, uci: &str) -> String {
+        let board = board(fen);
+        let m = GameState::uci_to_move(uci).unwrap();
+        to_chinese(&board, &m).unwrap()
+    }
+
+    fn uci(fen: &str, side: PieceSide, text: &str) -> String {
+        let m = from_chinese(&board(fen), side, text).unwrap();
+        GameState::move_to_uci(&m)
+    }
+
+    const START: &str = "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1";
+    // Two red elephants on the g-file and two red advisors on the f-file.
+    const TANDEM_GUARDS: &str = "3k5/9/9/9/9/6B2/9/5A3/9/4KAB2 w - - 0 1";
+
+    #[test]
+    fn opening_moves() {
+        assert_eq!(chinese(START, "h2e2"), "炮二平五");
+        assert_eq!(chinese(START, "h9g7"), "马8进7");
+        assert_eq!(chinese(START, "a0a1"), "车九进一");
+        assert_eq!(chinese(START, "i9i7"), "车9进2");
+        assert_eq!(chinese(START, "g3g4"), "兵三进一");
+        assert_eq!(chinese(START, "f0e1"), "仕四进五");
+        assert_eq!(chinese(START, "c9e7"), "象3进5");
+
+        assert_eq!(uci(START, PieceSide::Red, "炮二平五"), "h2e2");
+        assert_eq!(uci(START, PieceSide::Red, "傌8進7"), "b0c2");
+        assert_eq!(uci(START, PieceSide::Black, "马8进7"), "h9g7");
+        assert_eq!(uci(START, PieceSide::Black, "砲２平５"), "b7e7");
+    }
+
+    #[test]
+    fn tandem_pieces() {
+        // Two red chariots on the e-file, two black horses on the d-file.
+        let fen = "5k3/9/3n5/3n5/9/9/4R4/9/4R4/3K5 w - - 0 1";
+        assert_eq!(chinese(fen, "e3d3"), "前车平六");
+        assert_eq!(chinese(fen, "e1f1"), "后车平四");
+        assert_eq!(uci(fen, PieceSide::Red, "后车进一"), "e1e2");
+        assert_eq!(uci(fen, PieceSide::Red, "後車平四"), "e1f1");
+        assert_eq!(chinese(fen, "d6e4"), "前马进5");
+        assert_eq!(chinese(fen, "d7c9"), "后马退3");
+        assert_eq!(uci(fen, PieceSide::Black, "前马退2"), "d6b7");
+
+        // Advisors and elephants on one file keep the file number.
+        let fen = TANDEM_GUARDS;
+        assert_eq!(chinese(fen, "g4e2"), "相三退五");
+        assert_eq!(chinese(fen, "g0e2"), "相三进五");
+        assert_eq!(chinese(fen, "f0e1"), "仕四进五");
+        assert_eq!(chinese(fen, "f2e1"), "仕四退五");
+        assert_eq!(uci(fen, PieceSide::Red, "相三退五"), "g4e2");
+        assert_eq!(uci(fen, PieceSide::Red, "仕四退五"), "f2e1");
+    }
+
+    #[test]
+    fn tandem_pawns() {
+        // Three red pawns on one file.
+        let fen = "4k4/9/9/4P4/4P4/4P4/9/9/9/3K5 w - - 0 1";
+        assert_eq!(chinese(fen, "e6d6"), "前兵平六");
+        assert_eq!(chinese(fen, "e5f5"), "中兵平四");
+        assert_eq!(chinese(fen, "e4d4"), "后兵平六");
+        assert_eq!(uci(fen, PieceSide::Red, "中兵平四"), "e5f5");
+
+        // Four pawns on one file are numbered from the front.
+        let fen = "3k5/4P4/4P4/4P4/4P4/9/9/9/9/4K4 w - - 0 1";
+        assert_eq!(chinese(fen, "e8e9"), "一兵进一");
+        assert_eq!(chinese(fen, "e6d6"), "三兵平六");
+        assert_eq!(chinese(fen, "e5f5"), "四兵平四");
+        assert_eq!(uci(fen, PieceSide::Red, "二兵平四"), "e7f7");
+        assert_eq!(uci(fen, PieceSide::Red, "后兵平六"), "e5d5");
+
+        // Tandem pawns on two files name the file instead of the piece.
+        let fen = "3k5/9/9/2P1P4/2P1P4/9/9/9/9/4K4 w - - 0 1";
+        assert_eq!(chinese(fen, "c6c7"), "前七进一");
+        assert_eq!(chinese(fen, "e5f5"), "后五平四");
+        assert_eq!(uci(fen, PieceSide::Red, "前五平六"), "e6d6");
+    }
+
+    #[test]
+    fn wxf_moves() {
+        assert_eq!(
+            to_wxf(&board(START), &GameState::uci_to_move("h2e2").unwrap()).unwrap(),
+            "C2=5"
+        );
+        assert_eq!(
+            to_wxf(&board(START), &GameState::uci_to_move("h9g7").unwrap()).unwrap(),
+            "H8+7"
+        );
+        assert_eq!(
+            to_wxf(&board(START), &GameState::uci_to_move("f0e1").unwrap()).unwrap(),
+            "A4+5"
+        );
+
+        let m = from_wxf(&board(START), PieceSide::Red, "C2.5").unwrap();
+        assert_eq!(GameState::move_to_uci(&m), "h2e2");
+        let m = from_wxf(&board(START), PieceSide::Black, "n2+3").unwrap();
+        assert_eq!(GameState::move_to_uci(&m), "b9c7");
+
+        let fen = "5k3/9/3n5/3n5/9/9/4R4/9/4R4/3K5 w - - 0 1";
+        let m = GameState::uci_to_move("e3d3").unwrap();
+        assert_eq!(to_wxf(&board(fen), &m).unwrap(), "+R=6");
+        for text in ["-R+1", "R-+1"] {
+            let m = from_wxf(&board(fen), PieceSide::Red, text).unwrap();
+            assert_eq!(GameState::move_to_uci(&m), "e1e2");
+        }
+
+        let fen = "3k5/4P4/4P4/4P4/4P4/9/9/9/9/4K4 w - - 0 1";
+        let m = GameState::uci_to_move("e6d6").unwrap();
+        assert_eq!(to_wxf(&board(fen), &m).unwrap(), "cP=6");
+        let fen = "3k5/9/9/2P1P4/2P1P4/9/9/9/9/4K4 w - - 0 1";
+        let m = from_wxf(&board(fen), PieceSide::Red, "+5=6").unwrap();
+        assert_eq!(GameState::move_to_uci(&m), "e6d6");
+
+        // Advisors and elephants on one file keep the file number.
+        for (uci, text) in [("g4e2", "E3-5"), ("f0e1", "A4+5"), ("f2e1", "A4-5")] {
+            let m = GameState::uci_to_move(uci).unwrap();
+            assert_eq!(to_wxf(&board(TANDEM_GUARDS), &m).unwrap(), text);
+            let m = from_wxf(&board(TANDEM_GUARDS), PieceSide::Red, text).unwrap();
+            assert_eq!(GameState::move_to_uci(&m), uci);
+        }
+    }
+
+    #[test]
+    fn parses_any_notation() {
+        for text in ["h2e2", "炮二平五", "C2=5"] {
+            let m = parse_move(&board(START), PieceSide::Red, text).unwrap();
+            assert_eq!(GameState::move_to_uci(&m), "h2e2");
+        }
+        assert!(matches!(
+            parse_move(&board(START), PieceSide::Red, "h9g7"),
+            Err(NotationError::NoSuchPiece(_))
+        ));
+        assert!(matches!(
+            parse_move(&board(START), PieceSide::Red, "h2h8"),
+            Err(NotationError::IllegalMove(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(matches!(
+            from_chinese(&board(START), PieceSide::Red, "炮二"),
+            Err(NotationError::Malformed(_))
+        ));
+        assert!(matches!(
+            from_chinese(&board(START), PieceSide::Red, "炮五平四"),
+            Err(NotationError::NoSuchPiece(_))
+        ));
+        assert!(matches!(
+            from_chinese(&board(START), PieceSide::Red, "马二进四"),
+            Err(NotationError::IllegalMove(_))
+        ));
+    }
+
+    #[test]
+    fn every_legal_move_round_trips() {
+        let fens = [
+            START,
+            "r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1",
+            "3k5/9/9/2P1P4/2P1P4/9/9/9/9/4K4 w - - 0 1",
+            "5k3/9/3n5/3n5/9/9/4R4/9/4R4/3K5 b - - 0 1",
+        ];
+        for fen in fens {
+            let parsed = parse_fen(fen).unwrap();
+            for (from, to) in get_all_valid_moves(&parsed.board, parsed.side) {
+                let m = Move {
+                    from,
+                    to,
+                    captured: parsed.board[to.0][to.1],
+                };
+                let text = to_chinese(&parsed.board, &m).unwrap();
+                let back = from_chinese(&parsed.board, parsed.side, &text).unwrap();
+                assert_eq!((back.from, back.to), (from, to), "{} {}", fen, text);
+
+                let text = to_wxf(&parsed.board, &m).unwrap();
+                let back = from_wxf(&parsed.board, parsed.side, &text).unwrap();
+                assert_eq!((back.from, back.to), (from, to), "{} {}", fen, text);
+            }
+        }
+    }
+}
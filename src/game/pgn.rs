@@ -0,0 +1,840 @@
+//! Chinese Chess PGN, the dialect used by XQBase and most Xiangqi software.
+//!
+//! A game is a list of tag pairs followed by movetext:
+//!
+//! ```text
+//! [Game "Chinese Chess"]
+//! [Event "全国象棋个人赛"]
+//! [Red "甲"]
+//! [Black "乙"]
+//! [Result "1-0"]
+//! [Termination "resignation"]
+//! [FEN "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1"]
+//! [Format "Chinese"]
+//!
+//! 1. 炮二平五 马8进7 {屏风马} (1... 炮8平5 {顺炮})
+//! 2. 马二进三 1-0
+//! ```
+//!
+//! `Termination` tells how a decided game ended, so that the reason survives a round trip.
+//! `Format` names the notation of the moves: `ICCS` (H2-E2), `Chinese` (炮二平五) or
+//! `WXF` (C2.5). Moves are accepted in any of them regardless of the tag. `{...}` and `;`
+//! comments attach to the preceding move, and `(...)` holds alternatives to the preceding
+//! move.
+
+use crate::game::{
+    board::{self, BoardState},
+    fen::FenError,
+    notation::{self, Notation, NotationError},
+    piece::PieceSide,
+    record::GameMetadata,
+    state::{GameEndReason, GameState, GameStatus, Move},
+    tree::{GameTree, NodeId, ROOT},
+};
+
+/// File extension of PGN files.
+pub const PGN_EXTENSION: &str = "pgn";
+
+/// Errors that can occur when reading a PGN game.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PgnError {
+    Syntax { line: usize, message: String },
+    InvalidFen(FenError),
+    IllegalMove { line: usize, error: NotationError },
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnError::Syntax { line, message } => write!(f, "Line {}: {}", line, message),
+            PgnError::InvalidFen(e) => write!(f, "Invalid FEN tag: {}", e),
+            PgnError::IllegalMove { line, error } => write!(f, "Line {}: {}", line, error),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// A move in the movetext, with its comment and alternatives.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnMove {
+    pub text: String,
+    /// One-based line the move appears on.
+    pub line: usize,
+    pub comment: Option<String>,
+    /// Lines that could be played instead of this move.
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    fn new(text: String, line: usize) -> Self {
+        Self {
+            text,
+            line,
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+/// A parsed PGN game.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    /// Comment before the first move.
+    pub comment: Option<String>,
+    pub moves: Vec<PgnMove>,
+    /// Result token ending the movetext: 1-0, 0-1, 1/2-1/2 or *.
+    pub result: String,
+}
+
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Open,
+    Close,
+    Word(String),
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            chars: text.chars().peekable(),
+            line: 1,
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn syntax(&self, message: &str) -> PgnError {
+        PgnError::Syntax {
+            line: self.line,
+            message: message.to_string(),
+        }
+    }
+
+    /// Returns the next token and the line it starts on.
+    fn next_token(&mut self) -> Result<Option<(Token, usize)>, PgnError> {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.bump();
+        }
+        let line = self.line;
+        let Some(c) = self.bump() else {
+            return Ok(None);
+        };
+
+        let token = match c {
+            '[' => self.tag()?,
+            '{' => {
+                let mut text = String::new();
+                loop {
+                    match self.bump() {
+                        Some('}') => break,
+                        Some(c) => text.push(c),
+                        None => return Err(self.syntax("Unterminated comment")),
+                    }
+                }
+                Token::Comment(text.trim().to_string())
+            }
+            ';' => {
+                let mut text = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    text.push(c);
+                    self.bump();
+                }
+                Token::Comment(text.trim().to_string())
+            }
+            '(' => Token::Open,
+            ')' => Token::Close,
+            c => {
+                let mut word = c.to_string();
+                while let Some(&c) = self.chars.peek() {
+                    if c.is_whitespace() || "[]{}();".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    self.bump();
+                }
+                Token::Word(word)
+            }
+        };
+        Ok(Some((token, line)))
+    }
+
+    fn tag(&mut self) -> Result<Token, PgnError> {
+        let mut name = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || c == '"' || c == ']' {
+                break;
+            }
+            name.push(c);
+            self.bump();
+        }
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.bump();
+        }
+        if self.bump() != Some('"') {
+            return Err(self.syntax("Expected a quoted tag value"));
+        }
+
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('\\') => match self.bump() {
+                    Some(c) => value.push(c),
+                    None => return Err(self.syntax("Unterminated tag value")),
+                },
+                Some('"') => break,
+                Some('\n') | None => return Err(self.syntax("Unterminated tag value")),
+                Some(c) => value.push(c),
+            }
+        }
+        while let Some(c) = self.bump() {
+            if c == ']' {
+                return Ok(Token::Tag(name, value));
+            }
+            if !c.is_whitespace() {
+                break;
+            }
+        }
+        Err(self.syntax("Expected ']' after tag"))
+    }
+}
+
+/// Returns true for the tokens that end the movetext.
+fn is_result(word: &str) -> bool {
+    matches!(word, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+/// Strips a leading move number such as `12.` or `12...` from a word.
+fn strip_move_number(word: &str) -> &str {
+    let digits = word.len() - word.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 && word[digits..].starts_with('.') {
+        word[digits..].trim_start_matches('.')
+    } else {
+        word
+    }
+}
+
+/// Appends a comment to an optional existing one.
+fn append_comment(slot: &mut Option<String>, text: String) {
+    if text.is_empty() {
+        return;
+    }
+    match slot {
+        Some(existing) => {
+            existing.push('\n');
+            existing.push_str(&text);
+        }
+        None => *slot = Some(text),
+    }
+}
+
+/// Formats a comment as a `{...}` block. A brace comment cannot contain `}`, so any is
+/// written as the full-width `｝`.
+fn brace_comment(comment: &str) -> String {
+    format!("{{{}}}", comment.replace('}', "｝"))
+}
+
+/// Converts XQBase-style ICCS (H2-E2) to the lowercase form the rest of the game uses.
+fn normalize_move(text: &str) -> String {
+    let bytes = text.as_bytes();
+    if bytes.len() == 5 && bytes[2] == b'-' && text.is_ascii() {
+        format!("{}{}", &text[..2], &text[3..]).to_ascii_lowercase()
+    } else if bytes.len() == 4
+        && text.is_ascii()
+        && GameState::uci_to_move(&text.to_ascii_lowercase()).is_some()
+    {
+        text.to_ascii_lowercase()
+    } else {
+        text.to_string()
+    }
+}
+
+/// Returns the value of the Format tag for a notation.
+fn format_name(notation: Notation) -> &'static str {
+    match notation {
+        Notation::Iccs => "ICCS",
+        Notation::Chinese => "Chinese",
+        Notation::Wxf => "WXF",
+    }
+}
+
+/// Returns the result token for a game status.
+fn result_token(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::InProgress => "*",
+        GameStatus::RedWins(_) => "1-0",
+        GameStatus::BlackWins(_) => "0-1",
+        GameStatus::Draw(_) => "1/2-1/2",
+    }
+}
+
+/// Values of the Termination tag for each way a game can end.
+const TERMINATIONS: [(GameEndReason, &str); 11] = [
+    (GameEndReason::Checkmate, "checkmate"),
+    (GameEndReason::Stalemate, "stalemate"),
+    (GameEndReason::PerpetualCheck, "perpetual check"),
+    (GameEndReason::PerpetualChase, "perpetual chase"),
+    (GameEndReason::Repetition, "repetition"),
+    (GameEndReason::MoveLimit, "move limit"),
+    (GameEndReason::InsufficientMaterial, "insufficient material"),
+    (GameEndReason::Resignation, "resignation"),
+    (GameEndReason::Timeout, "time forfeit"),
+    (GameEndReason::Agreement, "agreement"),
+    (GameEndReason::Adjudication, "adjudication"),
+];
+
+/// Returns the Termination tag value for a reason.
+fn termination_name(reason: GameEndReason) -> &'static str {
+    TERMINATIONS
+        .iter()
+        .find(|(r, _)| *r == reason)
+        .map_or("adjudication", |(_, name)| name)
+}
+
+/// Returns the reason named by a Termination tag value. Unknown values, such as those of
+/// other programs, count as adjudication.
+fn termination_reason(name: &str) -> GameEndReason {
+    TERMINATIONS
+        .iter()
+        .find(|(_, n)| n.eq_ignore_ascii_case(name.trim()))
+        .map_or(GameEndReason::Adjudication, |(reason, _)| *reason)
+}
+
+/// Escapes a tag value.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl PgnGame {
+    /// Parses the first game in a PGN text.
+    pub fn parse(text: &str) -> Result<Self, PgnError> {
+        let mut lexer = Lexer::new(text.trim_start_matches('\u{feff}'));
+        let mut game = PgnGame {
+            result: "*".to_string(),
+            ..Default::default()
+        };
+        // The main line and any variations being read, innermost last.
+        let mut lines: Vec<Vec<PgnMove>> = vec![Vec::new()];
+        let mut pending_comment: Option<String> = None;
+
+        while let Some((token, line)) = lexer.next_token()? {
+            match token {
+                Token::Tag(name, value) => {
+                    if !lines[0].is_empty() || lines.len() > 1 {
+                        break;
+                    }
+                    game.tags.push((name, value));
+                }
+                Token::Comment(text) => {
+                    let in_variation = lines.len() > 1;
+                    let current = lines.last_mut().expect("main line is never popped");
+                    match current.last_mut() {
+                        Some(mv) => append_comment(&mut mv.comment, text),
+                        None if in_variation => append_comment(&mut pending_comment, text),
+                        None => append_comment(&mut game.comment, text),
+                    }
+                }
+                Token::Open => {
+                    if lines.last().is_none_or(|l| l.is_empty()) {
+                        return Err(PgnError::Syntax {
+                            line,
+                            message: "Variation does not follow a move".to_string(),
+                        });
+                    }
+                    lines.push(Vec::new());
+                }
+                Token::Close => {
+                    if lines.len() == 1 {
+                        return Err(PgnError::Syntax {
+                            line,
+                            message: "Unmatched ')'".to_string(),
+                        });
+                    }
+                    let variation = lines.pop().expect("checked above");
+                    if !variation.is_empty() {
+                        let parent = lines.last_mut().expect("main line is never popped");
+                        let mv = parent.last_mut().expect("variations follow a move");
+                        mv.variations.push(variation);
+                    }
+                }
+                Token::Word(word) => {
+                    if is_result(&word) {
+                        if lines.len() > 1 {
+                            continue;
+                        }
+                        game.result = word;
+                        break;
+                    }
+                    let word = strip_move_number(&word);
+                    if word.is_empty() || word.starts_with('$') {
+                        continue;
+                    }
+                    let text = word.trim_end_matches(['!', '?']);
+                    let mut mv = PgnMove::new(text.to_string(), line);
+                    mv.comment = pending_comment.take();
+                    lines
+                        .last_mut()
+                        .expect("main line is never popped")
+                        .push(mv);
+                }
+            }
+        }
+
+        if lines.len() > 1 {
+            return Err(PgnError::Syntax {
+                line: lexer.line,
+                message: "Unterminated variation".to_string(),
+            });
+        }
+        game.moves = lines.pop().expect("main line is never popped");
+        Ok(game)
+    }
+
+    /// Returns the value of a tag.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Replays the game through `make_move`, checking every variation along the way.
+    pub fn to_game(&self) -> Result<GameState, PgnError> {
+        let mut game = match self.tag("FEN") {
+            Some(fen) => GameState::from_fen(fen).map_err(PgnError::InvalidFen)?,
+            None => GameState::default(),
+        };
+        let tag = |name| self.tag(name).unwrap_or_default().to_string();
+        game.metadata = GameMetadata {
+            event: tag("Event"),
+            site: tag("Site"),
+            date: tag("Date").replace('.', "-"),
+            red: tag("Red"),
+            black: tag("Black"),
+        };
+
+        game.tree.set_comment(ROOT, self.comment.clone());
+        replay(&mut game, &self.moves)?;
+
+        if !game.status.is_over() {
+            let reason = self
+                .tag("Termination")
+                .map_or(GameEndReason::Adjudication, termination_reason);
+            let status = match self.tag("Result").unwrap_or(self.result.as_str()) {
+                "1-0" => GameStatus::RedWins(reason),
+                "0-1" => GameStatus::BlackWins(reason),
+                "1/2-1/2" => GameStatus::Draw(reason),
+                _ => GameStatus::InProgress,
+            };
+            game.set_result(status);
+        }
+        Ok(game)
+    }
+
+    /// Creates a PGN game from the moves played in a game.
+    pub fn from_game(game: &GameState, notation: Notation) -> Self {
+        let board = match &game.start_fen {
+            Some(fen) => GameState::from_fen(fen).unwrap_or_default().board,
+            None => board::init_board(),
+        };
+        let moves = game
+            .tree
+            .node(ROOT)
+            .children
+            .first()
+            .map_or_else(Vec::new, |&first| {
+                export_line(&game.tree, first, board, notation)
+            });
+
+        let status = game.final_status();
+        let result = result_token(status).to_string();
+        let metadata = &game.metadata;
+        let mut tags = vec![
+            ("Game".to_string(), "Chinese Chess".to_string()),
+            ("Event".to_string(), metadata.event.clone()),
+            ("Site".to_string(), metadata.site.clone()),
+            ("Date".to_string(), metadata.date.replace('-', ".")),
+            ("Red".to_string(), metadata.red.clone()),
+            ("Black".to_string(), metadata.black.clone()),
+            ("Result".to_string(), result.clone()),
+        ];
+        if let GameStatus::RedWins(reason)
+        | GameStatus::BlackWins(reason)
+        | GameStatus::Draw(reason) = status
+        {
+            tags.push((
+                "Termination".to_string(),
+                termination_name(reason).to_string(),
+            ));
+        }
+        if let Some(fen) = &game.start_fen {
+            tags.push(("FEN".to_string(), fen.clone()));
+        }
+        tags.push(("Format".to_string(), format_name(notation).to_string()));
+
+        Self {
+            tags,
+            comment: game.tree.node(ROOT).comment.clone(),
+            moves,
+            result,
+        }
+    }
+
+    /// Formats the game as PGN text.
+    pub fn to_pgn(&self) -> String {
+        let mut out = String::new();
+        for (name, value) in &self.tags {
+            out.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+        }
+        out.push('\n');
+        if let Some(comment) = &self.comment {
+            out.push_str(&brace_comment(comment));
+            out.push('\n');
+        }
+
+        let (number, side) = match self.tag("FEN").and_then(|f| GameState::from_fen(f).ok()) {
+            Some(start) => (start.fullmove_number, start.current_turn),
+            None => (1, PieceSide::Red),
+        };
+        write_moves(&mut out, &self.moves, number, side, true);
+        out.push_str(&self.result);
+        out.push('\n');
+        out
+    }
+}
+
+/// Plays a line of moves into the game tree, adding each variation from the position it
+/// branches from, and leaves the game at the end of the line. Moves are replayed without
+/// the automatic draw and loss rules; the Result tag says how the game ended.
+fn replay(game: &mut GameState, moves: &[PgnMove]) -> Result<(), PgnError> {
+    for mv in moves {
+        let illegal = |error| PgnError::IllegalMove {
+            line: mv.line,
+            error,
+        };
+        if game.status.is_over() {
+            return Err(illegal(NotationError::IllegalMove(mv.text.clone())));
+        }
+        let m: Move = game
+            .parse_move(&normalize_move(&mv.text))
+            .map_err(illegal)?;
+        let parent = game.current;
+        game.replay_move(m.from, m.to);
+        game.tree.set_comment(game.current, mv.comment.clone());
+
+        if !mv.variations.is_empty() {
+            for variation in &mv.variations {
+                game.go_to(parent);
+                replay(game, variation)?;
+            }
+            game.go_to(parent);
+            game.replay_move(m.from, m.to);
+        }
+    }
+    Ok(())
+}
+
+/// Formats a move played on the given board.
+fn move_text(board: &BoardState, m: &Move, notation: Notation) -> String {
+    match notation {
+        Notation::Iccs => {
+            let uci = GameState::move_to_uci(m).to_ascii_uppercase();
+            format!("{}-{}", &uci[..2], &uci[2..])
+        }
+        _ => notation::format_move(board, m, notation).unwrap_or_else(|| GameState::move_to_uci(m)),
+    }
+}
+
+/// Exports the line starting at `first`, played on `board`, following main-line children.
+/// Sibling variations are attached to each main-line move except the first of a variation.
+fn export_line(
+    tree: &GameTree,
+    first: NodeId,
+    mut board: BoardState,
+    notation: Notation,
+) -> Vec<PgnMove> {
+    let mut line = Vec::new();
+    let mut next = Some(first);
+    while let Some(id) = next {
+        let node = tree.node(id);
+        let Some(m) = node.mv else {
+            break;
+        };
+        let mut pgn_move = PgnMove::new(move_text(&board, &m, notation), 0);
+        pgn_move.comment = node.comment.clone();
+        if tree.variation_index(id) == 0
+            && let Some(parent) = node.parent
+        {
+            pgn_move.variations = tree.node(parent).children[1..]
+                .iter()
+                .map(|&variation| export_line(tree, variation, board, notation))
+                .collect();
+        }
+        line.push(pgn_move);
+        board[m.to.0][m.to.1] = board[m.from.0][m.from.1].take();
+        next = node.children.first().copied();
+    }
+    line
+}
+
+/// Writes a line of moves with move numbers, putting each main-line round on its own line.
+fn write_moves(
+    out: &mut String,
+    moves: &[PgnMove],
+    mut number: u32,
+    mut side: PieceSide,
+    main: bool,
+) {
+    let mut needs_number = true;
+    for mv in moves {
+        match side {
+            PieceSide::Red => out.push_str(&format!("{}. ", number)),
+            PieceSide::Black if needs_number => out.push_str(&format!("{}... ", number)),
+            PieceSide::Black => {}
+        }
+        out.push_str(&mv.text);
+        if let Some(comment) = &mv.comment {
+            out.push(' ');
+            out.push_str(&brace_comment(comment));
+        }
+        for variation in &mv.variations {
+            out.push_str(" (");
+            write_moves(out, variation, number, side, false);
+            out.push(')');
+        }
+        needs_number = mv.comment.is_some() || !mv.variations.is_empty();
+
+        if side == PieceSide::Black {
+            number += 1;
+            out.push(if main { '\n' } else { ' ' });
+        } else {
+            out.push(' ');
+        }
+        side = side.opponent();
+    }
+    if !main && out.ends_with(' ') {
+        out.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"[Game "Chinese Chess"]
+[Event "测试"]
+[Red "甲"]
+[Black "乙"]
+[Date "2026.10.17"]
+[Result "1-0"]
+[Format "Chinese"]
+
+{开局}
+1. 炮二平五 马8进7 {屏风马}
+   (1... 炮8平5 {顺炮} 2. 马二进三)
+2. 马二进三 ; 正着
+   车9平8 3. 车一平二 1-0
+"#;
+
+    #[test]
+    fn parses_tags_comments_and_variations() {
+        let pgn = PgnGame::parse(SAMPLE).unwrap();
+        assert_eq!(pgn.tag("event"), Some("测试"));
+        assert_eq!(pgn.comment.as_deref(), Some("开局"));
+        assert_eq!(pgn.result, "1-0");
+
+        let texts: Vec<&str> = pgn.moves.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            ["炮二平五", "马8进7", "马二进三", "车9平8", "车一平二"]
+        );
+        assert_eq!(pgn.moves[1].comment.as_deref(), Some("屏风马"));
+        assert_eq!(pgn.moves[2].comment.as_deref(), Some("正着"));
+        assert_eq!(pgn.moves[2].line, 12);
+
+        let variation = &pgn.moves[1].variations[0];
+        assert_eq!(variation.len(), 2);
+        assert_eq!(variation[0].text, "炮8平5");
+        assert_eq!(variation[0].comment.as_deref(), Some("顺炮"));
+    }
+
+    #[test]
+    fn imports_into_game_state() {
+        let mut game = PgnGame::parse(SAMPLE).unwrap().to_game().unwrap();
+        assert_eq!(game.moves_to_uci(), "h2e2 h9g7 h0g2 i9h9 i0h0");
+        assert_eq!(game.tree.node(ROOT).comment.as_deref(), Some("开局"));
+        let first = game.tree.node(ROOT).children[0];
+        let variation = game.tree.node(first).children[1];
+        assert_eq!(game.tree.node(variation).comment.as_deref(), Some("顺炮"));
+        assert_eq!(game.metadata.red, "甲");
+        assert_eq!(game.metadata.date, "2026-10-17");
+        assert_eq!(
+            game.status,
+            GameStatus::RedWins(GameEndReason::Adjudication)
+        );
+
+        assert!(game.go_to(variation));
+        assert_eq!(game.moves_to_uci(), "h2e2 h7e7");
+    }
+
+    #[test]
+    fn accepts_iccs_and_wxf_moves() {
+        let text = "[Format \"ICCS\"]\n1. H2-E2 H9-G7 2. h0g2 *";
+        let game = PgnGame::parse(text).unwrap().to_game().unwrap();
+        assert_eq!(game.moves_to_uci(), "h2e2 h9g7 h0g2");
+
+        let text = "[Format \"WXF\"]\n1. C2.5 H8+7 2. H2+3 *";
+        let game = PgnGame::parse(text).unwrap().to_game().unwrap();
+        assert_eq!(game.moves_to_uci(), "h2e2 h9g7 h0g2");
+        assert_eq!(game.status, GameStatus::InProgress);
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let mut game = GameState::from_fen(
+            "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR b - - 0 1",
+        )
+        .unwrap();
+        for uci in ["h9g7", "h2e2", "i9h9", "h0g2"] {
+            let m = GameState::uci_to_move(uci).unwrap();
+            game.make_move(m.from, m.to);
+        }
+        let main_end = game.current;
+        game.undo_last_move();
+        for uci in ["b0c2", "b9c7"] {
+            let m = GameState::uci_to_move(uci).unwrap();
+            game.make_move(m.from, m.to);
+        }
+        game.tree
+            .set_comment(game.current, Some("变着".to_string()));
+        game.go_to(main_end);
+        game.metadata.event = "对局 \"一\"".to_string();
+
+        for notation in [Notation::Chinese, Notation::Wxf, Notation::Iccs] {
+            let text = PgnGame::from_game(&game, notation).to_pgn();
+            assert!(text.contains("1... "), "{}", text);
+            let loaded = PgnGame::parse(&text).unwrap().to_game().unwrap();
+            assert_eq!(loaded.moves_to_uci(), game.moves_to_uci(), "{}", text);
+            assert_eq!(loaded.board, game.board);
+            assert_eq!(loaded.metadata.event, game.metadata.event);
+            assert!(text.contains("{变着}"), "{}", text);
+            assert_eq!(
+                PgnGame::from_game(&loaded, notation).to_pgn(),
+                text,
+                "variations survive a round trip"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_how_the_game_ended() {
+        let mut game = GameState::default();
+        for uci in ["h2e2", "h9g7"] {
+            let m = GameState::uci_to_move(uci).unwrap();
+            game.make_move(m.from, m.to);
+        }
+        for (status, termination) in [
+            (
+                GameStatus::BlackWins(GameEndReason::Resignation),
+                "resignation",
+            ),
+            (GameStatus::RedWins(GameEndReason::Timeout), "time forfeit"),
+            (GameStatus::Draw(GameEndReason::Agreement), "agreement"),
+        ] {
+            game.status = status;
+            let text = PgnGame::from_game(&game, Notation::Chinese).to_pgn();
+            assert!(
+                text.contains(&format!("[Termination \"{}\"]", termination)),
+                "{}",
+                text
+            );
+            let loaded = PgnGame::parse(&text).unwrap().to_game().unwrap();
+            assert_eq!(loaded.status, status);
+        }
+
+        // Saved while browsing an earlier move, the tags still tell how the game ended.
+        game.status = GameStatus::InProgress;
+        game.resign(PieceSide::Red);
+        game.go_to(ROOT);
+        let text = PgnGame::from_game(&game, Notation::Chinese).to_pgn();
+        assert!(text.contains("[Result \"0-1\"]"), "{}", text);
+        assert!(text.contains("[Termination \"resignation\"]"), "{}", text);
+
+        let text = "[Result \"0-1\"]\n[Termination \"abandoned\"]\n1. 炮二平五 0-1";
+        let loaded = PgnGame::parse(text).unwrap().to_game().unwrap();
+        assert_eq!(
+            loaded.status,
+            GameStatus::BlackWins(GameEndReason::Adjudication)
+        );
+    }
+
+    #[test]
+    fn closing_braces_in_comments_are_replaced() {
+        let mut game = GameState::default();
+        game.tree.set_comment(ROOT, Some("{开局}".to_string()));
+        for uci in ["h2e2", "h9g7", "h0g2"] {
+            let m = GameState::uci_to_move(uci).unwrap();
+            game.make_move(m.from, m.to);
+        }
+        let first = game.tree.node(ROOT).children[0];
+        game.tree
+            .set_comment(first, Some("中炮} 1. 车一平二".to_string()));
+
+        let text = PgnGame::from_game(&game, Notation::Chinese).to_pgn();
+        let loaded = PgnGame::parse(&text).unwrap().to_game().unwrap();
+        assert_eq!(loaded.moves_to_uci(), game.moves_to_uci(), "{}", text);
+        assert_eq!(loaded.tree.node(ROOT).comment.as_deref(), Some("{开局｝"));
+        let first = loaded.tree.node(ROOT).children[0];
+        assert_eq!(
+            loaded.tree.node(first).comment.as_deref(),
+            Some("中炮｝ 1. 车一平二")
+        );
+        assert_eq!(
+            PgnGame::from_game(&loaded, Notation::Chinese).to_pgn(),
+            text
+        );
+    }
+
+    #[test]
+    fn imports_games_that_go_on_after_a_repetition() {
+        // The opening position occurs a third time after move 4, yet the game continued.
+        let text = "1. 马二进三 马8进7 2. 马三退二 马7退8 3. 马二进三 马8进7 4. 马三退二 马7退8\n\
+                    5. 炮二平五 马8进7 1-0";
+        let game = PgnGame::parse(text).unwrap().to_game().unwrap();
+        assert_eq!(game.move_history.len(), 10);
+        assert_eq!(
+            game.status,
+            GameStatus::RedWins(GameEndReason::Adjudication)
+        );
+    }
+
+    #[test]
+    fn reports_line_of_illegal_moves() {
+        let text = "[Event \"x\"]\n\n1. 炮二平五 马8进7\n(1... 马8进9 2. 炮五进五 3. 车一进一)\n*";
+        let error = PgnGame::parse(text).unwrap().to_game().err().unwrap();
+        assert!(
+            matches!(error, PgnError::IllegalMove { line: 4, .. }),
+            "{:?}",
+            error
+        );
+
+        let error = PgnGame::parse("1. 炮二平五 (马8进7").unwrap_err();
+        assert!(matches!(error, PgnError::Syntax { .. }));
+    }
+}
@@ -48,3 +48,67 @@ impl Piece {
         }
     }
 }
+
+impl PieceKind {
+    /// Returns the position of this kind in tables indexed by piece kind.
+    pub fn index(&self) -> usize {
+        match self {
+            PieceKind::Jiang => 0,
+            PieceKind::Shi => 1,
+            PieceKind::Xiang => 2,
+            PieceKind::Ma => 3,
+            PieceKind::Ju => 4,
+            PieceKind::Pao => 5,
+            PieceKind::Zu => 6,
+        }
+    }
+}
+
+impl PieceSide {
+    /// Returns the opposing side.
+    pub fn opponent(&self) -> PieceSide {
+        match self {
+            PieceSide::Red => PieceSide::Black,
+            PieceSide::Black => PieceSide::Red,
+        }
+    }
+}
+
+impl Piece {
+    /// Returns the FEN letter for this piece (uppercase for Red, lowercase for Black).
+    pub fn fen_char(&self) -> char {
+        let c = match self.kind {
+            PieceKind::Jiang => 'k',
+            PieceKind::Shi => 'a',
+            PieceKind::Xiang => 'b',
+            PieceKind::Ma => 'n',
+            PieceKind::Ju => 'r',
+            PieceKind::Pao => 'c',
+            PieceKind::Zu => 'p',
+        };
+        match self.side {
+            PieceSide::Red => c.to_ascii_uppercase(),
+            PieceSide::Black => c,
+        }
+    }
+
+    /// Parses a FEN letter into a piece. Accepts the `e`/`h` aliases for elephant and horse.
+    pub fn from_fen_char(c: char) -> Option<Piece> {
+        let kind = match c.to_ascii_lowercase() {
+            'k' => PieceKind::Jiang,
+            'a' => PieceKind::Shi,
+            'b' | 'e' => PieceKind::Xiang,
+            'n' | 'h' => PieceKind::Ma,
+            'r' => PieceKind::Ju,
+            'c' => PieceKind::Pao,
+            'p' => PieceKind::Zu,
+            _ => return None,
+        };
+        let side = if c.is_ascii_uppercase() {
+            PieceSide::Red
+        } else {
+            PieceSide::Black
+        };
+        Some(Piece { side, kind })
+    }
+}
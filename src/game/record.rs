@@ -0,0 +1,369 @@
+//! Saved game records.
+//!
+//! A record is stored as [RON](https://github.com/ron-rs/ron) text:
+//!
+//! ```text
+//! (
+//!     version: 2,
+//!     metadata: (
+//!         event: "",
+//!         site: "",
+//!         date: "2026-10-17",
+//!         red: "玩家",
+//!         black: "引擎",
+//!     ),
+//!     start_fen: None,
+//!     comment: None,
+//!     moves: [
+//!         (mv: "h2e2", comment: Some("中炮"), variations: [[(mv: "b2e2")]]),
+//!         (mv: "h9g7"),
+//!     ],
+//!     result: InProgress,
+//! )
+//! ```
+//!
+//! `start_fen` is the FEN of the starting position, or `None` for the standard opening.
+//! `comment` comes before the first move. `moves` is the main line in ICCS coordinates;
+//! each move may carry a comment and lines played instead of it. Every line is replayed
+//! through [`GameState::make_move`] on load, so a record with an illegal move is rejected.
+//! `result` keeps outcomes the moves alone cannot reproduce, such as a resignation.
+//!
+//! Files with the `.pgn` extension are read and written as PGN instead; see [`crate::game::pgn`].
+//! XQF files can be opened but not saved; see [`crate::game::xqf`].
+
+use crate::game::{
+    fen::FenError,
+    notation::Notation,
+    pgn::{PGN_EXTENSION, PgnError, PgnGame},
+    state::{GameState, GameStatus},
+    tree::{GameTree, NodeId, ROOT},
+    xqf::{self, XQF_EXTENSION, XqfError},
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Version written to new records.
+pub const RECORD_VERSION: u32 = 2;
+
+/// File extension of saved records.
+pub const RECORD_EXTENSION: &str = "ron";
+
+/// Descriptive information about a game.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameMetadata {
+    pub event: String,
+    pub site: String,
+    /// Date the game was played, as YYYY-MM-DD.
+    pub date: String,
+    pub red: String,
+    pub black: String,
+}
+
+/// Errors that can occur when reading or writing a game record.
+#[derive(Debug)]
+pub enum RecordError {
+    Io(std::io::Error),
+    Format(String),
+    UnsupportedVersion(u32),
+    InvalidFen(FenError),
+    Pgn(PgnError),
+    Xqf(XqfError),
+    /// A move that is malformed or illegal, with its zero-based ply from the start position.
+    IllegalMove {
+        index: usize,
+        text: String,
+    },
+}
+
+impl std::fmt::Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordError::Io(e) => write!(f, "{}", e),
+            RecordError::Format(e) => write!(f, "Invalid game record: {}", e),
+            RecordError::UnsupportedVersion(v) => {
+                write!(f, "Unsupported game record version {}", v)
+            }
+            RecordError::InvalidFen(e) => write!(f, "Invalid start position: {}", e),
+            RecordError::Pgn(e) => write!(f, "{}", e),
+            RecordError::Xqf(e) => write!(f, "{}", e),
+            RecordError::IllegalMove { index, text } => {
+                write!(f, "Move {} '{}' is not legal", index + 1, text)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl From<std::io::Error> for RecordError {
+    fn from(e: std::io::Error) -> Self {
+        RecordError::Io(e)
+    }
+}
+
+/// A move of a record, with its comment and the lines that could be played instead.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordMove {
+    /// ICCS coordinates of the move.
+    pub mv: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variations: Vec<Vec<RecordMove>>,
+}
+
+/// A game as stored on disk.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub version: u32,
+    #[serde(default)]
+    pub metadata: GameMetadata,
+    #[serde(default)]
+    pub start_fen: Option<String>,
+    #[serde(default)]
+    pub comment: Option<String>,
+    pub moves: Vec<RecordMove>,
+    #[serde(default)]
+    pub result: GameStatus,
+}
+
+impl GameRecord {
+    /// Creates a record of a game, including its variations and comments.
+    pub fn from_game(game: &GameState) -> Self {
+        Self {
+            version: RECORD_VERSION,
+            metadata: game.metadata.clone(),
+            start_fen: game.start_fen.clone(),
+            comment: game.tree.node(ROOT).comment.clone(),
+            moves: game
+                .tree
+                .node(ROOT)
+                .children
+                .first()
+                .map_or_else(Vec::new, |&first| export_line(&game.tree, first)),
+            result: game.final_status(),
+        }
+    }
+
+    /// Rebuilds the game by replaying every line from the start position.
+    pub fn to_game(&self) -> Result<GameState, RecordError> {
+        if self.version > RECORD_VERSION {
+            return Err(RecordError::UnsupportedVersion(self.version));
+        }
+
+        let mut game = match &self.start_fen {
+            Some(fen) => GameState::from_fen(fen).map_err(RecordError::InvalidFen)?,
+            None => GameState::default(),
+        };
+        game.metadata = self.metadata.clone();
+        game.tree.set_comment(ROOT, self.comment.clone());
+
+        replay(&mut game, &self.moves, 0)?;
+
+        if !game.status.is_over() {
+            game.set_result(self.result);
+        }
+        Ok(game)
+    }
+
+    /// Parses a record from RON text.
+    pub fn from_ron(text: &str) -> Result<Self, RecordError> {
+        let format = |e: ron::error::SpannedError| RecordError::Format(e.to_string());
+        ron::from_str(text).map_err(format)
+    }
+
+    /// Formats the record as RON text.
+    pub fn to_ron(&self) -> Result<String, RecordError> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| RecordError::Format(e.to_string()))
+    }
+}
+
+/// Plays a line into the game tree, adding each variation from the position it branches
+/// from. `ply` is the number of moves before the line starts. Moves are replayed without
+/// the automatic draw and loss rules; `result` says how the game ended.
+fn replay(game: &mut GameState, moves: &[RecordMove], ply: usize) -> Result<(), RecordError> {
+    for (offset, mv) in moves.iter().enumerate() {
+        let index = ply + offset;
+        let illegal = || RecordError::IllegalMove {
+            index,
+            text: mv.mv.clone(),
+        };
+        if game.status.is_over() {
+            return Err(illegal());
+        }
+        let m = GameState::uci_to_move(&mv.mv).ok_or_else(illegal)?;
+        let parent = game.current;
+        if !game.replay_move(m.from, m.to) {
+            return Err(illegal());
+        }
+        game.tree.set_comment(game.current, mv.comment.clone());
+
+        if !mv.variations.is_empty() {
+            for variation in &mv.variations {
+                game.go_to(parent);
+                replay(game, variation, index)?;
+            }
+            game.go_to(parent);
+            game.replay_move(m.from, m.to);
+        }
+    }
+    Ok(())
+}
+
+/// Exports the line starting at `first`, attaching sibling variations to main-line moves.
+fn export_line(tree: &GameTree, first: NodeId) -> Vec<RecordMove> {
+    let mut line = Vec::new();
+    let mut next = Some(first);
+    while let Some(id) = next {
+        let node = tree.node(id);
+        let Some(m) = node.mv else {
+            break;
+        };
+        let mut variations = Vec::new();
+        if tree.variation_index(id) == 0
+            && let Some(parent) = node.parent
+        {
+            variations = tree.node(parent).children[1..]
+                .iter()
+                .map(|&variation| export_line(tree, variation))
+                .collect();
+        }
+        line.push(RecordMove {
+            mv: GameState::move_to_uci(&m),
+            comment: node.comment.clone(),
+            variations,
+        });
+        next = node.children.first().copied();
+    }
+    line
+}
+
+/// Returns true if the path has the given extension.
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+/// Reads a game from a record, PGN or XQF file.
+pub fn load_game(path: &Path) -> Result<GameState, RecordError> {
+    let bytes = std::fs::read(path)?;
+    if has_extension(path, XQF_EXTENSION) {
+        return xqf::load_xqf(&bytes).map_err(RecordError::Xqf);
+    }
+    let text = String::from_utf8_lossy(&bytes);
+    if has_extension(path, PGN_EXTENSION) {
+        PgnGame::parse(&text)
+            .and_then(|pgn| pgn.to_game())
+            .map_err(RecordError::Pgn)
+    } else {
+        GameRecord::from_ron(&text)?.to_game()
+    }
+}
+
+/// Writes a game to a record or PGN file, writing PGN moves in the given notation.
+pub fn save_game(game: &GameState, path: &Path, notation: Notation) -> Result<(), RecordError> {
+    let text = if has_extension(path, PGN_EXTENSION) {
+        PgnGame::from_game(game, notation).to_pgn()
+    } else {
+        GameRecord::from_game(game).to_ron()?
+    };
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+/// Returns today's date as YYYY-MM-DD in UTC.
+pub fn today() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let days = (secs / 86_400) as i64;
+
+    // Civil-from-days conversion for the proleptic Gregorian calendar.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::piece::PieceSide;
+
+    #[test]
+    fn record_round_trips_through_ron() {
+        let mut game = GameState::from_fen(
+            "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR b - - 0 1",
+        )
+        .unwrap();
+        for uci in ["h9g7", "h2e2", "b7e7"] {
+            let m = GameState::uci_to_move(uci).unwrap();
+            game.make_move(m.from, m.to);
+        }
+        let main_end = game.current;
+        game.undo_last_move();
+        let m = GameState::uci_to_move("h2d2").unwrap();
+        game.make_move(m.from, m.to);
+        game.tree
+            .set_comment(game.current, Some("过宫炮".to_string()));
+        game.go_to(main_end);
+        game.metadata.red = "甲".to_string();
+        game.resign(PieceSide::Black);
+        let result = game.status;
+        // Saved while browsing: the result is still the one at the end of the main line.
+        game.go_to(ROOT);
+
+        let text = GameRecord::from_game(&game).to_ron().unwrap();
+        let loaded = GameRecord::from_ron(&text).unwrap().to_game().unwrap();
+        assert_eq!(loaded.status, result);
+        game.go_to(main_end);
+        assert_eq!(loaded.board, game.board);
+        assert_eq!(loaded.current_turn, game.current_turn);
+        assert_eq!(loaded.hash, game.hash);
+        assert_eq!(loaded.moves_to_uci(), game.moves_to_uci());
+        assert_eq!(loaded.status, game.status);
+        assert_eq!(loaded.metadata, game.metadata);
+        assert_eq!(GameRecord::from_game(&loaded).to_ron().unwrap(), text);
+    }
+
+    #[test]
+    fn records_go_on_after_a_repetition() {
+        let moves = ["h0g2", "h9g7", "g2h0", "g7h9"]
+            .repeat(2)
+            .into_iter()
+            .chain(["h2e2"])
+            .map(|mv| format!("(mv: \"{}\")", mv))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let text = format!("(version: 2, moves: [{}], result: InProgress)", moves);
+        let game = GameRecord::from_ron(&text).unwrap().to_game().unwrap();
+        assert_eq!(game.move_history.len(), 9);
+        assert_eq!(game.status, GameStatus::InProgress);
+    }
+
+    #[test]
+    fn illegal_moves_are_rejected() {
+        let record =
+            GameRecord::from_ron(r#"(version: 2, moves: [(mv: "h2e2"), (mv: "h2e2")])"#).unwrap();
+        assert!(matches!(
+            record.to_game(),
+            Err(RecordError::IllegalMove { index: 1, .. })
+        ));
+        let record = GameRecord::from_ron(
+            r#"(version: 2, moves: [(mv: "h2e2", variations: [[(mv: "b2e2"), (mv: "b2e2")]])])"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            record.to_game(),
+            Err(RecordError::IllegalMove { index: 1, .. })
+        ));
+    }
+}
@@ -51,59 +51,156 @@ pub fn count_pieces_between(board: &BoardState, from: Position, to: Position) ->
     }
 }
 
-fn is_valid_jiang_move(_board: &BoardState, from: Position, to: Position, side: PieceSide) -> bool {
-    let (r1, c1) = from;
-    let (r2, c2) = to;
+/// Why a move cannot be played.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IllegalMove {
+    OffBoard,
+    NoPiece,
+    NotYourPiece,
+    CaptureOwnPiece,
+    /// The piece does not move along that line or that far.
+    WrongShape(PieceKind),
+    LeavesPalace,
+    /// An elephant may not cross the river.
+    CrossesRiver,
+    HorseLegBlocked,
+    ElephantEyeBlocked,
+    /// A piece stands between a chariot or non-capturing cannon and its target.
+    PathBlocked,
+    /// A cannon captures only by jumping exactly one piece.
+    NoCannonScreen,
+    TooManyScreens,
+    PawnBackward,
+    /// A pawn may only move sideways after crossing the river.
+    PawnSideways,
+    /// The move would leave the two generals facing on an open file.
+    FlyingGeneral,
+    LeavesGeneralInCheck,
+}
 
-    if !is_within_palace(to, side) {
-        return false;
+impl IllegalMove {
+    /// Returns the Chinese explanation shown to the player.
+    pub fn label(&self) -> &'static str {
+        match self {
+            IllegalMove::OffBoard => "超出棋盘",
+            IllegalMove::NoPiece => "此处无子",
+            IllegalMove::NotYourPiece => "不是己方棋子",
+            IllegalMove::CaptureOwnPiece => "不能吃己方棋子",
+            IllegalMove::WrongShape(kind) => match kind {
+                PieceKind::Jiang => "将帅每次直走一步",
+                PieceKind::Shi => "士只能斜走一步",
+                PieceKind::Xiang => "象走田字",
+                PieceKind::Ma => "马走日字",
+                PieceKind::Ju => "车走直线",
+                PieceKind::Pao => "炮走直线",
+                PieceKind::Zu => "兵卒每次只走一步",
+            },
+            IllegalMove::LeavesPalace => "不能走出九宫",
+            IllegalMove::CrossesRiver => "象不能过河",
+            IllegalMove::HorseLegBlocked => "蹩马腿",
+            IllegalMove::ElephantEyeBlocked => "塞象眼",
+            IllegalMove::PathBlocked => "中间有子阻挡",
+            IllegalMove::NoCannonScreen => "炮吃子须隔一子",
+            IllegalMove::TooManyScreens => "炮只能隔一子吃子",
+            IllegalMove::PawnBackward => "兵卒不能后退",
+            IllegalMove::PawnSideways => "兵卒过河前不能横走",
+            IllegalMove::FlyingGeneral => "将帅不能照面",
+            IllegalMove::LeavesGeneralInCheck => "不能送将",
+        }
     }
-    let row_diff = (r1 as i32 - r2 as i32).abs();
-    let col_diff = (c1 as i32 - c2 as i32).abs();
+}
 
-    (row_diff == 1 && col_diff == 0) || (row_diff == 0 && col_diff == 1)
+impl std::fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IllegalMove::OffBoard => write!(f, "The square is off the board"),
+            IllegalMove::NoPiece => write!(f, "There is no piece to move"),
+            IllegalMove::NotYourPiece => write!(f, "The piece belongs to the opponent"),
+            IllegalMove::CaptureOwnPiece => write!(f, "A piece cannot capture its own side"),
+            IllegalMove::WrongShape(kind) => write!(f, "{:?} does not move that way", kind),
+            IllegalMove::LeavesPalace => write!(f, "The piece cannot leave the palace"),
+            IllegalMove::CrossesRiver => write!(f, "Elephants cannot cross the river"),
+            IllegalMove::HorseLegBlocked => write!(f, "The horse's leg is blocked"),
+            IllegalMove::ElephantEyeBlocked => write!(f, "The elephant's eye is blocked"),
+            IllegalMove::PathBlocked => write!(f, "A piece blocks the way"),
+            IllegalMove::NoCannonScreen => write!(f, "The cannon has no screen to jump"),
+            IllegalMove::TooManyScreens => write!(f, "The cannon can only jump one piece"),
+            IllegalMove::PawnBackward => write!(f, "Pawns cannot move backward"),
+            IllegalMove::PawnSideways => {
+                write!(f, "Pawns cannot move sideways before crossing the river")
+            }
+            IllegalMove::FlyingGeneral => write!(f, "The generals would face each other"),
+            IllegalMove::LeavesGeneralInCheck => write!(f, "The move leaves the general in check"),
+        }
+    }
 }
 
-fn is_valid_shi_move(_board: &BoardState, from: Position, to: Position, side: PieceSide) -> bool {
+impl std::error::Error for IllegalMove {}
+
+fn check_jiang_move(from: Position, to: Position, side: PieceSide) -> Result<(), IllegalMove> {
     let (r1, c1) = from;
     let (r2, c2) = to;
 
-    if !is_within_palace(to, side) {
-        return false;
-    }
     let row_diff = (r1 as i32 - r2 as i32).abs();
     let col_diff = (c1 as i32 - c2 as i32).abs();
-
-    row_diff == 1 && col_diff == 1
+    if !((row_diff == 1 && col_diff == 0) || (row_diff == 0 && col_diff == 1)) {
+        return Err(IllegalMove::WrongShape(PieceKind::Jiang));
+    }
+    if !is_within_palace(to, side) {
+        return Err(IllegalMove::LeavesPalace);
+    }
+    Ok(())
 }
 
-fn is_valid_xiang_move(board: &BoardState, from: Position, to: Position, side: PieceSide) -> bool {
+fn check_shi_move(from: Position, to: Position, side: PieceSide) -> Result<(), IllegalMove> {
     let (r1, c1) = from;
     let (r2, c2) = to;
 
-    if !is_own_side(to, side) {
-        return false;
+    let row_diff = (r1 as i32 - r2 as i32).abs();
+    let col_diff = (c1 as i32 - c2 as i32).abs();
+    if row_diff != 1 || col_diff != 1 {
+        return Err(IllegalMove::WrongShape(PieceKind::Shi));
+    }
+    if !is_within_palace(to, side) {
+        return Err(IllegalMove::LeavesPalace);
     }
+    Ok(())
+}
+
+fn check_xiang_move(
+    board: &BoardState,
+    from: Position,
+    to: Position,
+    side: PieceSide,
+) -> Result<(), IllegalMove> {
+    let (r1, c1) = from;
+    let (r2, c2) = to;
 
     let row_diff = (r1 as i32 - r2 as i32).abs();
     let col_diff = (c1 as i32 - c2 as i32).abs();
     if row_diff != 2 || col_diff != 2 {
-        return false;
+        return Err(IllegalMove::WrongShape(PieceKind::Xiang));
+    }
+    if !is_own_side(to, side) {
+        return Err(IllegalMove::CrossesRiver);
     }
 
     let eye_row = (r1 + r2) / 2;
     let eye_col = (c1 + c2) / 2;
-    board[eye_row][eye_col].is_none()
+    if board[eye_row][eye_col].is_some() {
+        return Err(IllegalMove::ElephantEyeBlocked);
+    }
+    Ok(())
 }
 
-fn is_valid_ma_move(board: &BoardState, from: Position, to: Position) -> bool {
+fn check_ma_move(board: &BoardState, from: Position, to: Position) -> Result<(), IllegalMove> {
     let (r1, c1) = from;
     let (r2, c2) = to;
 
     let row_diff = (r1 as i32 - r2 as i32).abs();
     let col_diff = (c1 as i32 - c2 as i32).abs();
     if !((row_diff == 2 && col_diff == 1) || (row_diff == 1 && col_diff == 2)) {
-        return false;
+        return Err(IllegalMove::WrongShape(PieceKind::Ma));
     }
 
     let (leg_row, leg_col) = if row_diff == 2 {
@@ -113,51 +210,44 @@ fn is_valid_ma_move(board: &BoardState, from: Position, to: Position) -> bool {
         let leg_c = if c2 > c1 { c1 + 1 } else { c1 - 1 };
         (r1, leg_c)
     };
-    board[leg_row][leg_col].is_none()
-}
-
-fn is_valid_ju_move(board: &BoardState, from: Position, to: Position) -> bool {
-    let (r1, c1) = from;
-    let (r2, c2) = to;
-
-    if r1 != r2 && c1 != c2 {
-        return false;
+    if board[leg_row][leg_col].is_some() {
+        return Err(IllegalMove::HorseLegBlocked);
     }
-
-    count_pieces_between(board, from, to) == Some(0)
+    Ok(())
 }
 
-fn is_valid_pao_move(board: &BoardState, from: Position, to: Position) -> bool {
-    let (r1, c1) = from;
-    let (r2, c2) = to;
-
-    if r1 != r2 && c1 != c2 {
-        return false;
+fn check_ju_move(board: &BoardState, from: Position, to: Position) -> Result<(), IllegalMove> {
+    match count_pieces_between(board, from, to) {
+        Some(0) => Ok(()),
+        Some(_) => Err(IllegalMove::PathBlocked),
+        None => Err(IllegalMove::WrongShape(PieceKind::Ju)),
     }
+}
 
-    let pieces_between = match count_pieces_between(board, from, to) {
-        Some(count) => count,
-        None => return false,
+fn check_pao_move(board: &BoardState, from: Position, to: Position) -> Result<(), IllegalMove> {
+    let Some(pieces_between) = count_pieces_between(board, from, to) else {
+        return Err(IllegalMove::WrongShape(PieceKind::Pao));
     };
 
     let is_capture = board[to.0][to.1].is_some();
 
-    if is_capture {
-        pieces_between == 1
-    } else {
-        pieces_between == 0
+    match (is_capture, pieces_between) {
+        (true, 1) | (false, 0) => Ok(()),
+        (true, 0) => Err(IllegalMove::NoCannonScreen),
+        (true, _) => Err(IllegalMove::TooManyScreens),
+        (false, _) => Err(IllegalMove::PathBlocked),
     }
 }
 
-fn is_valid_zu_move(_board: &BoardState, from: Position, to: Position, side: PieceSide) -> bool {
+fn check_zu_move(from: Position, to: Position, side: PieceSide) -> Result<(), IllegalMove> {
     let (r1, c1) = from;
     let (r2, c2) = to;
 
     let row_diff = r2 as i32 - r1 as i32;
     let col_diff = (c1 as i32 - c2 as i32).abs();
     let row_move = row_diff.abs();
-    if row_move > 1 || col_diff > 1 || (row_move == 0 && col_diff == 0) {
-        return false;
+    if row_move + col_diff != 1 {
+        return Err(IllegalMove::WrongShape(PieceKind::Zu));
     }
 
     let forward = match side {
@@ -165,197 +255,983 @@ fn is_valid_zu_move(_board: &BoardState, from: Position, to: Position, side: Pie
         PieceSide::Black => 1,
     };
     if row_diff == -forward {
-        return false;
+        return Err(IllegalMove::PawnBackward);
     }
-
-    if has_crossed_river(from, side) {
-        (row_diff == forward && col_diff == 0) || (row_diff == 0 && col_diff == 1)
-    } else {
-        row_diff == forward && col_diff == 0
+    if col_diff == 1 && !has_crossed_river(from, side) {
+        return Err(IllegalMove::PawnSideways);
     }
+    Ok(())
+}
+
+pub fn find_general(board: &BoardState, side: PieceSide) -> Option<Position> {
+    let rows = match side {
+        PieceSide::Red => 7..=9,
+        PieceSide::Black => 0..=2,
+    };
+
+    let general = Some(Piece {
+        side,
+        kind: PieceKind::Jiang,
+    });
+    rows.flat_map(|row| (3..=5).map(move |col| (row, col)))
+        .find(|&(row, col)| board[row][col] == general)
 }
 
-pub fn are_generals_facing(board: &BoardState) -> bool {
-    let mut red_jiang: Option<Position> = None;
-    let mut black_jiang: Option<Position> = None;
+pub fn is_under_attack(board: &BoardState, pos: Position, defender_side: PieceSide) -> bool {
+    let attacker_side = defender_side.opponent();
 
-    for row in 7..=9 {
-        for col in 3..=5 {
-            if let Some(piece) = board[row][col] {
-                if piece.kind == PieceKind::Jiang && piece.side == PieceSide::Red {
-                    red_jiang = Some((row, col));
-                }
+    for (row, rank) in board.iter().enumerate() {
+        for (col, square) in rank.iter().enumerate() {
+            if let Some(piece) = *square
+                && piece.side == attacker_side
+                && is_valid_piece_move(board, (row, col), pos, piece)
+            {
+                return true;
             }
         }
     }
 
-    for row in 0..=2 {
-        for col in 3..=5 {
-            if let Some(piece) = board[row][col] {
-                if piece.kind == PieceKind::Jiang && piece.side == PieceSide::Black {
-                    black_jiang = Some((row, col));
+    false
+}
+
+pub fn is_in_check(board: &BoardState, side: PieceSide) -> bool {
+    match find_general(board, side) {
+        Some(general_pos) => is_general_attacked(board, general_pos, side),
+        None => false,
+    }
+}
+
+fn check_piece_move(
+    board: &BoardState,
+    from: Position,
+    to: Position,
+    piece: Piece,
+) -> Result<(), IllegalMove> {
+    match piece.kind {
+        PieceKind::Jiang => check_jiang_move(from, to, piece.side),
+        PieceKind::Shi => check_shi_move(from, to, piece.side),
+        PieceKind::Xiang => check_xiang_move(board, from, to, piece.side),
+        PieceKind::Ma => check_ma_move(board, from, to),
+        PieceKind::Ju => check_ju_move(board, from, to),
+        PieceKind::Pao => check_pao_move(board, from, to),
+        PieceKind::Zu => check_zu_move(from, to, piece.side),
+    }
+}
+
+fn is_valid_piece_move(board: &BoardState, from: Position, to: Position, piece: Piece) -> bool {
+    check_piece_move(board, from, to, piece).is_ok()
+}
+
+/// Checks a move for `side`, explaining why it cannot be played if it is illegal.
+pub fn check_move(
+    board: &BoardState,
+    from: Position,
+    to: Position,
+    side: PieceSide,
+) -> Result<(), IllegalMove> {
+    if !is_within_board(from) || !is_within_board(to) {
+        return Err(IllegalMove::OffBoard);
+    }
+
+    let piece = board[from.0][from.1].ok_or(IllegalMove::NoPiece)?;
+    if piece.side != side {
+        return Err(IllegalMove::NotYourPiece);
+    }
+
+    if let Some(target_piece) = board[to.0][to.1]
+        && target_piece.side == side
+    {
+        return Err(IllegalMove::CaptureOwnPiece);
+    }
+
+    check_piece_move(board, from, to, piece)?;
+
+    if SearchBoard::new(board).is_legal(from, to, side) {
+        return Ok(());
+    }
+    let mut after = *board;
+    after[to.0][to.1] = after[from.0][from.1].take();
+    match (
+        find_general(&after, PieceSide::Red),
+        find_general(&after, PieceSide::Black),
+    ) {
+        (Some(red), Some(black))
+            if red.1 == black.1 && count_pieces_between(&after, red, black) == Some(0) =>
+        {
+            Err(IllegalMove::FlyingGeneral)
+        }
+        _ => Err(IllegalMove::LeavesGeneralInCheck),
+    }
+}
+
+pub fn is_valid_move(board: &BoardState, from: Position, to: Position, side: PieceSide) -> bool {
+    check_move(board, from, to, side).is_ok()
+}
+
+pub fn get_valid_moves(board: &BoardState, from: Position, side: PieceSide) -> Vec<Position> {
+    let mut valid_moves = Vec::new();
+    match board[from.0][from.1] {
+        Some(piece) if piece.side == side => {
+            SearchBoard::new(board).legal_moves_from(from, &mut valid_moves);
+        }
+        _ => {}
+    }
+    valid_moves
+}
+
+pub fn get_all_valid_moves(board: &BoardState, side: PieceSide) -> Vec<(Position, Position)> {
+    let mut all_moves = Vec::new();
+    SearchBoard::new(board).legal_moves(side, &mut all_moves);
+    all_moves
+}
+
+pub fn is_checkmate(board: &BoardState, side: PieceSide) -> bool {
+    let mut search = SearchBoard::new(board);
+    search.is_in_check(side) && !search.has_legal_move(side)
+}
+
+pub fn is_stalemate(board: &BoardState, side: PieceSide) -> bool {
+    let mut search = SearchBoard::new(board);
+    !search.is_in_check(side) && !search.has_legal_move(side)
+}
+
+const ORTHOGONAL: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const DIAGONAL: [(i32, i32); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+/// Horse jumps paired with the leg square that must be empty.
+const HORSE_JUMPS: [((i32, i32), (i32, i32)); 8] = [
+    ((-2, -1), (-1, 0)),
+    ((-2, 1), (-1, 0)),
+    ((2, -1), (1, 0)),
+    ((2, 1), (1, 0)),
+    ((-1, -2), (0, -1)),
+    ((1, -2), (0, -1)),
+    ((-1, 2), (0, 1)),
+    ((1, 2), (0, 1)),
+];
+
+/// Returns the square at the given offset, or None if it falls off the board.
+fn offset(pos: Position, (dr, dc): (i32, i32)) -> Option<Position> {
+    let row = pos.0 as i32 + dr;
+    let col = pos.1 as i32 + dc;
+    if (0..10).contains(&row) && (0..9).contains(&col) {
+        Some((row as usize, col as usize))
+    } else {
+        None
+    }
+}
+
+/// Returns the direction an unmoved pawn of the given side advances in.
+fn forward(side: PieceSide) -> i32 {
+    match side {
+        PieceSide::Red => -1,
+        PieceSide::Black => 1,
+    }
+}
+
+/// Returns true if the general of `side` at `general` is attacked, including by the
+/// opposing general along an open file. Scans outward from the general instead of
+/// testing every enemy piece.
+fn is_general_attacked(board: &BoardState, general: Position, side: PieceSide) -> bool {
+    let enemy = side.opponent();
+    let is_enemy =
+        |pos: Position, kind: PieceKind| board[pos.0][pos.1] == Some(Piece { side: enemy, kind });
+
+    for dir in ORTHOGONAL {
+        let mut pos = general;
+        let mut screened = false;
+        while let Some(next) = offset(pos, dir) {
+            pos = next;
+            if board[pos.0][pos.1].is_none() {
+                continue;
+            }
+            if screened {
+                if is_enemy(pos, PieceKind::Pao) {
+                    return true;
                 }
+                break;
+            }
+            if is_enemy(pos, PieceKind::Ju) || is_enemy(pos, PieceKind::Jiang) {
+                return true;
             }
+            screened = true;
         }
     }
 
-    if let (Some((r1, c1)), Some((r2, c2))) = (red_jiang, black_jiang) {
-        if c1 == c2 {
-            let (min_r, max_r) = (r2, r1);
-            for row in (min_r + 1)..max_r {
-                if board[row][c1].is_some() {
-                    return false;
-                }
+    // A horse attacks the general when the square diagonally between them is empty.
+    for diag in DIAGONAL {
+        let Some(leg) = offset(general, diag) else {
+            continue;
+        };
+        if board[leg.0][leg.1].is_some() {
+            continue;
+        }
+        for jump in [(diag.0 * 2, diag.1), (diag.0, diag.1 * 2)] {
+            if let Some(pos) = offset(general, jump)
+                && is_enemy(pos, PieceKind::Ma)
+            {
+                return true;
             }
-            return true;
         }
     }
 
-    false
+    let pawn_dirs = [(-forward(enemy), 0), (0, -1), (0, 1)];
+    pawn_dirs
+        .into_iter()
+        .filter_map(|dir| offset(general, dir))
+        .any(|pos| is_enemy(pos, PieceKind::Zu))
 }
 
-pub fn find_general(board: &BoardState, side: PieceSide) -> Option<Position> {
-    let rows = match side {
-        PieceSide::Red => 7..=9,
-        PieceSide::Black => 0..=2,
-    };
+/// A board that tracks both generals and applies moves in place, for fast legal move
+/// generation. Moves are generated pseudo-legally per piece and then filtered by making
+/// each move, testing whether the mover's general is attacked, and unmaking it.
+#[derive(Clone)]
+pub struct SearchBoard {
+    pub board: BoardState,
+    generals: [Option<Position>; 2],
+}
 
-    for row in rows {
-        for col in 3..=5 {
-            if let Some(piece) = board[row][col] {
-                if piece.kind == PieceKind::Jiang && piece.side == side {
-                    return Some((row, col));
+impl SearchBoard {
+    pub fn new(board: &BoardState) -> Self {
+        let mut generals = [None; 2];
+        for (row, rank) in board.iter().enumerate() {
+            for (col, square) in rank.iter().enumerate() {
+                if let Some(piece) = square
+                    && piece.kind == PieceKind::Jiang
+                {
+                    generals[piece.side as usize] = Some((row, col));
                 }
             }
         }
+        Self {
+            board: *board,
+            generals,
+        }
     }
-    None
-}
 
-pub fn is_under_attack(board: &BoardState, pos: Position, defender_side: PieceSide) -> bool {
-    let attacker_side = match defender_side {
-        PieceSide::Red => PieceSide::Black,
-        PieceSide::Black => PieceSide::Red,
-    };
+    /// Moves a piece without any validation, returning the captured piece.
+    pub fn make_move(&mut self, from: Position, to: Position) -> Option<Piece> {
+        let piece = self.board[from.0][from.1].take();
+        let captured = self.board[to.0][to.1].replace(piece?);
+        if let Some(p) = piece
+            && p.kind == PieceKind::Jiang
+        {
+            self.generals[p.side as usize] = Some(to);
+        }
+        if let Some(c) = captured
+            && c.kind == PieceKind::Jiang
+        {
+            self.generals[c.side as usize] = None;
+        }
+        captured
+    }
 
-    for row in 0..10 {
-        for col in 0..9 {
-            if let Some(piece) = board[row][col] {
-                if piece.side == attacker_side {
-                    if is_valid_piece_move(board, (row, col), pos, piece) {
-                        return true;
+    /// Reverts a move made with `make_move`.
+    pub fn unmake_move(&mut self, from: Position, to: Position, captured: Option<Piece>) {
+        let piece = self.board[to.0][to.1].take();
+        self.board[from.0][from.1] = piece;
+        self.board[to.0][to.1] = captured;
+        if let Some(p) = piece
+            && p.kind == PieceKind::Jiang
+        {
+            self.generals[p.side as usize] = Some(from);
+        }
+        if let Some(c) = captured
+            && c.kind == PieceKind::Jiang
+        {
+            self.generals[c.side as usize] = Some(to);
+        }
+    }
+
+    pub fn is_in_check(&self, side: PieceSide) -> bool {
+        match self.generals[side as usize] {
+            Some(general) => is_general_attacked(&self.board, general, side),
+            None => false,
+        }
+    }
+
+    /// Returns true if the pseudo-legal move does not leave the mover's general attacked.
+    pub fn is_legal(&mut self, from: Position, to: Position, side: PieceSide) -> bool {
+        let captured = self.make_move(from, to);
+        let legal = !self.is_in_check(side);
+        self.unmake_move(from, to, captured);
+        legal
+    }
+
+    /// Appends the destinations the piece at `from` could move to, ignoring checks.
+    pub fn pseudo_legal_moves_from(&self, from: Position, out: &mut Vec<Position>) {
+        let Some(piece) = self.board[from.0][from.1] else {
+            return;
+        };
+        let side = piece.side;
+        let board = &self.board;
+        let can_land = |pos: Position| board[pos.0][pos.1].is_none_or(|p| p.side != side);
+        let push_if = |pos: Option<Position>, out: &mut Vec<Position>| {
+            if let Some(pos) = pos
+                && can_land(pos)
+            {
+                out.push(pos);
+            }
+        };
+
+        match piece.kind {
+            PieceKind::Jiang => {
+                for dir in ORTHOGONAL {
+                    push_if(
+                        offset(from, dir).filter(|&p| is_within_palace(p, side)),
+                        out,
+                    );
+                }
+            }
+            PieceKind::Shi => {
+                for dir in DIAGONAL {
+                    push_if(
+                        offset(from, dir).filter(|&p| is_within_palace(p, side)),
+                        out,
+                    );
+                }
+            }
+            PieceKind::Xiang => {
+                for dir in DIAGONAL {
+                    let eye_open = offset(from, dir).is_some_and(|e| board[e.0][e.1].is_none());
+                    let to = offset(from, (dir.0 * 2, dir.1 * 2))
+                        .filter(|&p| eye_open && is_own_side(p, side));
+                    push_if(to, out);
+                }
+            }
+            PieceKind::Ma => {
+                for (jump, leg) in HORSE_JUMPS {
+                    let leg_open = offset(from, leg).is_some_and(|l| board[l.0][l.1].is_none());
+                    push_if(offset(from, jump).filter(|_| leg_open), out);
+                }
+            }
+            PieceKind::Ju => {
+                for dir in ORTHOGONAL {
+                    let mut pos = from;
+                    while let Some(next) = offset(pos, dir) {
+                        pos = next;
+                        push_if(Some(pos), out);
+                        if board[pos.0][pos.1].is_some() {
+                            break;
+                        }
+                    }
+                }
+            }
+            PieceKind::Pao => {
+                for dir in ORTHOGONAL {
+                    let mut pos = from;
+                    let mut screened = false;
+                    while let Some(next) = offset(pos, dir) {
+                        pos = next;
+                        match board[pos.0][pos.1] {
+                            None if !screened => out.push(pos),
+                            None => {}
+                            Some(_) if !screened => screened = true,
+                            Some(_) => {
+                                push_if(Some(pos), out);
+                                break;
+                            }
+                        }
                     }
                 }
             }
+            PieceKind::Zu => {
+                push_if(offset(from, (forward(side), 0)), out);
+                if has_crossed_river(from, side) {
+                    push_if(offset(from, (0, -1)), out);
+                    push_if(offset(from, (0, 1)), out);
+                }
+            }
         }
     }
 
-    false
-}
+    /// Appends the legal destinations of the piece at `from`.
+    pub fn legal_moves_from(&mut self, from: Position, out: &mut Vec<Position>) {
+        let Some(piece) = self.board[from.0][from.1] else {
+            return;
+        };
+        let start = out.len();
+        self.pseudo_legal_moves_from(from, out);
+        let mut i = start;
+        while i < out.len() {
+            if self.is_legal(from, out[i], piece.side) {
+                i += 1;
+            } else {
+                out.swap_remove(i);
+            }
+        }
+    }
 
-pub fn is_in_check(board: &BoardState, side: PieceSide) -> bool {
-    if let Some(general_pos) = find_general(board, side) {
-        is_under_attack(board, general_pos, side)
-    } else {
+    /// Appends every legal move for `side`.
+    pub fn legal_moves(&mut self, side: PieceSide, out: &mut Vec<(Position, Position)>) {
+        let mut targets = Vec::with_capacity(17);
+        for from in self.pieces_of(side) {
+            targets.clear();
+            self.legal_moves_from(from, &mut targets);
+            out.extend(targets.iter().map(|&to| (from, to)));
+        }
+    }
+
+    /// Returns true if `side` has at least one legal move.
+    pub fn has_legal_move(&mut self, side: PieceSide) -> bool {
+        let mut targets = Vec::with_capacity(17);
+        for from in self.pieces_of(side) {
+            targets.clear();
+            self.pseudo_legal_moves_from(from, &mut targets);
+            if targets.iter().any(|&to| self.is_legal(from, to, side)) {
+                return true;
+            }
+        }
         false
     }
-}
 
-fn is_valid_piece_move(board: &BoardState, from: Position, to: Position, piece: Piece) -> bool {
-    match piece.kind {
-        PieceKind::Jiang => is_valid_jiang_move(board, from, to, piece.side),
-        PieceKind::Shi => is_valid_shi_move(board, from, to, piece.side),
-        PieceKind::Xiang => is_valid_xiang_move(board, from, to, piece.side),
-        PieceKind::Ma => is_valid_ma_move(board, from, to),
-        PieceKind::Ju => is_valid_ju_move(board, from, to),
-        PieceKind::Pao => is_valid_pao_move(board, from, to),
-        PieceKind::Zu => is_valid_zu_move(board, from, to, piece.side),
+    fn pieces_of(&self, side: PieceSide) -> Vec<Position> {
+        let mut pieces = Vec::with_capacity(16);
+        for (row, rank) in self.board.iter().enumerate() {
+            for (col, square) in rank.iter().enumerate() {
+                if square.is_some_and(|p| p.side == side) {
+                    pieces.push((row, col));
+                }
+            }
+        }
+        pieces
     }
 }
 
-pub fn is_valid_move(board: &BoardState, from: Position, to: Position, side: PieceSide) -> bool {
-    if !is_within_board(from) || !is_within_board(to) {
-        return false;
+/// Rough material value used to decide whether an attack on a protected piece still counts as a chase.
+fn chase_value(kind: PieceKind) -> u32 {
+    match kind {
+        PieceKind::Ju => 9,
+        PieceKind::Ma | PieceKind::Pao => 4,
+        PieceKind::Shi | PieceKind::Xiang => 2,
+        PieceKind::Zu | PieceKind::Jiang => 1,
     }
+}
 
-    let piece = match board[from.0][from.1] {
-        Some(p) => p,
-        None => return false,
+/// Returns true if the legal capture from `from` to `to` chases the captured piece; see
+/// [`chased_pieces`].
+fn is_chasing_capture(board: &BoardState, from: Position, to: Position, side: PieceSide) -> bool {
+    let (Some(attacker), Some(target)) = (board[from.0][from.1], board[to.0][to.1]) else {
+        return false;
     };
-    if piece.side != side {
+    if matches!(attacker.kind, PieceKind::Jiang | PieceKind::Zu)
+        || target.kind == PieceKind::Jiang
+        || (target.kind == PieceKind::Zu && !has_crossed_river(to, target.side))
+    {
         return false;
     }
 
-    if let Some(target_piece) = board[to.0][to.1] {
-        if target_piece.side == side {
-            return false;
-        }
+    chase_value(target.kind) > chase_value(attacker.kind) || {
+        let mut test_board = *board;
+        test_board[to.0][to.1] = test_board[from.0][from.1].take();
+        !is_under_attack(&test_board, to, side)
     }
+}
 
-    if !is_valid_piece_move(board, from, to, piece) {
-        return false;
+/// Returns the positions of enemy pieces that `side` is currently chasing.
+///
+/// A piece is chased when it can be legally captured by a piece other than the general or a
+/// pawn, and it is either unprotected or worth more than the attacker (e.g. a chariot attacked
+/// by a horse). The enemy general and pawns that have not crossed the river cannot be chased.
+pub fn chased_pieces(board: &BoardState, side: PieceSide) -> Vec<Position> {
+    let mut chased = Vec::new();
+
+    for (from, to) in get_all_valid_moves(board, side) {
+        if !chased.contains(&to) && is_chasing_capture(board, from, to, side) {
+            chased.push(to);
+        }
     }
 
-    let mut test_board = *board;
-    test_board[to.0][to.1] = test_board[from.0][from.1];
-    test_board[from.0][from.1] = None;
+    chased
+}
 
-    if are_generals_facing(&test_board) {
-        return false;
-    }
+/// Returns true if `side` is chasing the enemy piece at `target`. Cheaper than
+/// [`chased_pieces`] when only a few squares matter.
+pub fn is_chased(board: &BoardState, target: Position, side: PieceSide) -> bool {
+    (0..10)
+        .flat_map(|row| (0..9).map(move |col| (row, col)))
+        .filter(|&(row, col)| board[row][col].is_some_and(|piece| piece.side == side))
+        .any(|from| {
+            is_valid_move(board, from, target, side)
+                && is_chasing_capture(board, from, target, side)
+        })
+}
 
-    if is_in_check(&test_board, side) {
-        return false;
+/// Returns true if neither side has a piece that can cross the river, so no checkmate is possible.
+pub fn has_insufficient_material(board: &BoardState) -> bool {
+    board.iter().flatten().flatten().all(|piece| {
+        matches!(
+            piece.kind,
+            PieceKind::Jiang | PieceKind::Shi | PieceKind::Xiang
+        )
+    })
+}
+
+/// Something that makes a position impossible to reach in a real game.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PositionProblem {
+    MissingGeneral(PieceSide),
+    /// A side has more pieces of a kind than the game starts with.
+    TooManyPieces {
+        side: PieceSide,
+        kind: PieceKind,
+        count: usize,
+    },
+    /// A piece stands on a square it can never reach.
+    IllegalSquare {
+        piece: Piece,
+        pos: Position,
+    },
+    GeneralsFacing,
+    /// The side that just moved left its general in check.
+    OpponentInCheck,
+}
+
+impl PositionProblem {
+    /// Returns the Chinese description of this problem.
+    pub fn label(&self) -> String {
+        match self {
+            PositionProblem::MissingGeneral(PieceSide::Red) => "缺少帅".to_string(),
+            PositionProblem::MissingGeneral(PieceSide::Black) => "缺少将".to_string(),
+            PositionProblem::TooManyPieces { side, kind, .. } => {
+                format!(
+                    "{}过多",
+                    Piece {
+                        side: *side,
+                        kind: *kind
+                    }
+                    .label()
+                )
+            }
+            PositionProblem::IllegalSquare { piece, .. } => {
+                format!("{}位置不合法", piece.label())
+            }
+            PositionProblem::GeneralsFacing => "将帅对面".to_string(),
+            PositionProblem::OpponentInCheck => "非走子方被将军".to_string(),
+        }
     }
+}
 
-    true
+impl std::fmt::Display for PositionProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PositionProblem::MissingGeneral(side) => write!(f, "{:?} has no general", side),
+            PositionProblem::TooManyPieces { side, kind, count } => {
+                write!(f, "{:?} has {} pieces of kind {:?}", side, count, kind)
+            }
+            PositionProblem::IllegalSquare { piece, pos } => write!(
+                f,
+                "{} cannot stand on row {}, column {}",
+                piece.fen_char(),
+                pos.0,
+                pos.1
+            ),
+            PositionProblem::GeneralsFacing => write!(f, "The generals face each other"),
+            PositionProblem::OpponentInCheck => {
+                write!(f, "The side not to move is in check")
+            }
+        }
+    }
 }
 
-pub fn get_valid_moves(board: &BoardState, from: Position, side: PieceSide) -> Vec<Position> {
-    let mut valid_moves = Vec::new();
+/// Most pieces of a kind each side starts with.
+fn starting_count(kind: PieceKind) -> usize {
+    match kind {
+        PieceKind::Jiang => 1,
+        PieceKind::Zu => 5,
+        _ => 2,
+    }
+}
 
-    if let Some(piece) = board[from.0][from.1] {
-        if piece.side != side {
-            return valid_moves;
+/// Returns true if a piece can ever stand on the given square.
+fn is_reachable_square(piece: Piece, pos: Position) -> bool {
+    let (row, col) = pos;
+    // Rows counted from the piece's own back rank.
+    let rank = match piece.side {
+        PieceSide::Red => 9 - row,
+        PieceSide::Black => row,
+    };
+    match piece.kind {
+        PieceKind::Jiang => is_within_palace(pos, piece.side),
+        PieceKind::Shi => is_within_palace(pos, piece.side) && (rank + col) % 2 == 1,
+        PieceKind::Xiang => {
+            is_own_side(pos, piece.side) && rank % 2 == 0 && col % 2 == 0 && (rank + col) % 4 == 2
         }
-    } else {
-        return valid_moves;
+        PieceKind::Zu => has_crossed_river(pos, piece.side) || (rank >= 3 && col % 2 == 0),
+        PieceKind::Ma | PieceKind::Ju | PieceKind::Pao => true,
     }
+}
+
+/// Checks that a position could arise in a game with `side_to_move` to play, returning every
+/// problem found. An empty list means the position is valid.
+pub fn validate_position(board: &BoardState, side_to_move: PieceSide) -> Vec<PositionProblem> {
+    let mut problems = Vec::new();
 
-    for row in 0..10 {
-        for col in 0..9 {
-            if is_valid_move(board, from, (row, col), side) {
-                valid_moves.push((row, col));
+    for side in [PieceSide::Red, PieceSide::Black] {
+        let mut counts = [0usize; 7];
+        let mut generals = 0;
+        for (row, rank) in board.iter().enumerate() {
+            for (col, square) in rank.iter().enumerate() {
+                let Some(piece) = *square else {
+                    continue;
+                };
+                if piece.side != side {
+                    continue;
+                }
+                counts[piece.kind.index()] += 1;
+                if piece.kind == PieceKind::Jiang {
+                    generals += 1;
+                }
+                if !is_reachable_square(piece, (row, col)) {
+                    problems.push(PositionProblem::IllegalSquare {
+                        piece,
+                        pos: (row, col),
+                    });
+                }
+            }
+        }
+        if generals == 0 {
+            problems.push(PositionProblem::MissingGeneral(side));
+        }
+        for kind in [
+            PieceKind::Jiang,
+            PieceKind::Shi,
+            PieceKind::Xiang,
+            PieceKind::Ma,
+            PieceKind::Ju,
+            PieceKind::Pao,
+            PieceKind::Zu,
+        ] {
+            let count = counts[kind.index()];
+            if count > starting_count(kind) {
+                problems.push(PositionProblem::TooManyPieces { side, kind, count });
             }
         }
     }
 
-    valid_moves
+    if let (Some(red), Some(black)) = (
+        find_general(board, PieceSide::Red),
+        find_general(board, PieceSide::Black),
+    ) && red.1 == black.1
+        && count_pieces_between(board, red, black) == Some(0)
+    {
+        problems.push(PositionProblem::GeneralsFacing);
+    } else if is_in_check(board, side_to_move.opponent()) {
+        problems.push(PositionProblem::OpponentInCheck);
+    }
+
+    problems
 }
 
-pub fn get_all_valid_moves(board: &BoardState, side: PieceSide) -> Vec<(Position, Position)> {
-    let mut all_moves = Vec::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::fen::parse_placement;
+    use crate::game::state::GameState;
 
-    for row in 0..10 {
-        for col in 0..9 {
-            if let Some(piece) = board[row][col] {
-                if piece.side == side {
-                    let from = (row, col);
-                    for to in get_valid_moves(board, from, side) {
-                        all_moves.push((from, to));
+    /// Builds a board from a FEN placement field.
+    fn board(placement: &str) -> BoardState {
+        parse_placement(placement).unwrap()
+    }
+
+    /// Converts an ICCS square such as "e0" into a board position.
+    fn sq(iccs: &str) -> Position {
+        GameState::uci_to_pos(iccs).unwrap()
+    }
+
+    fn perft(fen: &str, depth: u32) -> u64 {
+        GameState::from_fen(fen).unwrap().perft(depth)
+    }
+
+    const START: &str = "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1";
+
+    #[test]
+    fn perft_start_position() {
+        assert_eq!(perft(START, 1), 44);
+        assert_eq!(perft(START, 2), 1920);
+        assert_eq!(perft(START, 3), 79666);
+    }
+
+    #[test]
+    #[ignore = "slow in debug builds"]
+    fn perft_start_position_deep() {
+        assert_eq!(perft(START, 4), 3290240);
+    }
+
+    // Positions with their perft counts at depths 1 to 4.
+    const PERFT_POSITIONS: [(&str, [u64; 4]); 6] = [
+        (
+            "r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1",
+            [38, 1128, 43929, 1339047],
+        ),
+        (
+            "1cbak4/9/n2a5/2p1p3p/5cp2/2n2N3/6PCP/3AB4/2C6/3A1K1N1 w - - 0 1",
+            [7, 281, 8620, 326201],
+        ),
+        (
+            "5a3/3k5/3aR4/9/5r3/5n3/9/3A1A3/5K3/2BC2B2 w - - 0 1",
+            [25, 424, 9850, 202884],
+        ),
+        (
+            "CRN1k1b2/3ca4/4ba3/9/2nr5/9/9/4B4/4A4/4KA3 w - - 0 1",
+            [28, 516, 14808, 395483],
+        ),
+        (
+            "R1N1k1b2/9/3aba3/9/2nr5/2B6/9/4B4/4A4/4KA3 w - - 0 1",
+            [21, 364, 7626, 162837],
+        ),
+        (
+            "CnN1k1b2/c3a4/4ba3/9/2nr5/9/9/4C4/4A4/4KA3 w - - 0 1",
+            [19, 583, 11714, 376467],
+        ),
+    ];
+
+    #[test]
+    fn perft_test_positions() {
+        for (fen, counts) in PERFT_POSITIONS {
+            for (depth, expected) in (1..=3).zip(counts) {
+                assert_eq!(perft(fen, depth), expected, "{} at depth {}", fen, depth);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "slow in debug builds"]
+    fn perft_test_positions_deep() {
+        for (fen, counts) in PERFT_POSITIONS {
+            assert_eq!(perft(fen, 4), counts[3], "{} at depth 4", fen);
+        }
+    }
+
+    #[test]
+    fn generator_matches_single_move_validation() {
+        let game = GameState::from_fen(
+            "r1ba1a3/4kn3/2n1b4/pNp1p1p1p/4c4/6P2/P1P2R2P/1CcC5/9/2BAKAB2 w - - 0 1",
+        )
+        .unwrap();
+        for side in [PieceSide::Red, PieceSide::Black] {
+            let generated = get_all_valid_moves(&game.board, side);
+            for row in 0..10 {
+                for col in 0..9 {
+                    for to_row in 0..10 {
+                        for to_col in 0..9 {
+                            let mv = ((row, col), (to_row, to_col));
+                            assert_eq!(
+                                is_valid_move(&game.board, mv.0, mv.1, side),
+                                generated.contains(&mv),
+                                "{:?}",
+                                mv
+                            );
+                        }
                     }
                 }
             }
         }
     }
 
-    all_moves
-}
+    #[test]
+    fn horse_leg_blocks_move() {
+        let b = board("4k4/9/9/9/9/9/9/4P4/4N4/3K5");
+        assert!(!is_valid_move(&b, sq("e1"), sq("d3"), PieceSide::Red));
+        assert!(!is_valid_move(&b, sq("e1"), sq("f3"), PieceSide::Red));
+        assert!(is_valid_move(&b, sq("e1"), sq("g2"), PieceSide::Red));
+        assert!(is_valid_move(&b, sq("e1"), sq("c0"), PieceSide::Red));
+    }
 
-pub fn is_checkmate(board: &BoardState, side: PieceSide) -> bool {
-    is_in_check(board, side) && get_all_valid_moves(board, side).is_empty()
-}
+    #[test]
+    fn elephant_eye_and_river() {
+        let b = board("4k4/9/9/9/9/2B6/9/9/9/3K5");
+        assert!(is_valid_move(&b, sq("c4"), sq("a2"), PieceSide::Red));
+        assert!(is_valid_move(&b, sq("c4"), sq("e2"), PieceSide::Red));
+        // The river is the edge of the elephant's own half.
+        assert!(!is_valid_move(&b, sq("c4"), sq("e6"), PieceSide::Red));
+        assert!(!is_valid_move(&b, sq("c4"), sq("a6"), PieceSide::Red));
 
-pub fn is_stalemate(board: &BoardState, side: PieceSide) -> bool {
-    !is_in_check(board, side) && get_all_valid_moves(board, side).is_empty()
+        let blocked = board("4k4/9/9/9/9/9/9/9/3N5/2BK5");
+        assert!(!is_valid_move(&blocked, sq("c0"), sq("e2"), PieceSide::Red));
+        assert!(is_valid_move(&blocked, sq("c0"), sq("a2"), PieceSide::Red));
+    }
+
+    #[test]
+    fn advisor_and_general_stay_in_palace() {
+        let b = board("4k4/9/9/9/9/9/9/5A3/9/3K5");
+        assert!(!is_valid_move(&b, sq("f2"), sq("g3"), PieceSide::Red));
+        assert!(is_valid_move(&b, sq("f2"), sq("e1"), PieceSide::Red));
+        assert!(!is_valid_move(&b, sq("f2"), sq("f1"), PieceSide::Red));
+        assert!(!is_valid_move(&b, sq("d0"), sq("c0"), PieceSide::Red));
+        assert!(!is_valid_move(&b, sq("d0"), sq("e1"), PieceSide::Red));
+        assert!(is_valid_move(&b, sq("d0"), sq("d1"), PieceSide::Red));
+    }
+
+    #[test]
+    fn cannon_needs_exactly_one_screen_to_capture() {
+        let b = board("5k3/9/9/9/4r4/9/4p4/9/4C4/3K5");
+        assert!(!is_valid_move(&b, sq("e1"), sq("e3"), PieceSide::Red));
+        assert!(is_valid_move(&b, sq("e1"), sq("e5"), PieceSide::Red));
+        assert!(!is_valid_move(&b, sq("e1"), sq("e4"), PieceSide::Red));
+        assert!(is_valid_move(&b, sq("e1"), sq("e2"), PieceSide::Red));
+
+        let no_screen = board("5k3/9/9/9/9/9/4r4/9/4C4/3K5");
+        assert!(!is_valid_move(
+            &no_screen,
+            sq("e1"),
+            sq("e3"),
+            PieceSide::Red
+        ));
+    }
+
+    #[test]
+    fn chariot_is_blocked_by_pieces() {
+        let b = board("3k5/9/9/9/9/9/9/R1P6/9/4K4");
+        assert!(is_valid_move(&b, sq("a2"), sq("b2"), PieceSide::Red));
+        assert!(!is_valid_move(&b, sq("a2"), sq("d2"), PieceSide::Red));
+        assert!(is_valid_move(&b, sq("a2"), sq("a9"), PieceSide::Red));
+        assert!(!is_valid_move(&b, sq("a2"), sq("b3"), PieceSide::Red));
+    }
+
+    #[test]
+    fn pawn_moves_sideways_only_after_crossing_river() {
+        let b = board("3k5/9/9/9/2P6/6P2/9/9/9/4K4");
+        assert!(is_valid_move(&b, sq("g4"), sq("g5"), PieceSide::Red));
+        assert!(!is_valid_move(&b, sq("g4"), sq("f4"), PieceSide::Red));
+        assert!(!is_valid_move(&b, sq("g4"), sq("g3"), PieceSide::Red));
+        assert!(is_valid_move(&b, sq("c5"), sq("b5"), PieceSide::Red));
+        assert!(is_valid_move(&b, sq("c5"), sq("c6"), PieceSide::Red));
+        assert!(!is_valid_move(&b, sq("c5"), sq("c4"), PieceSide::Red));
+    }
+
+    #[test]
+    fn generals_may_not_face_each_other() {
+        let b = board("4k4/9/9/9/9/9/9/9/9/3K5");
+        assert!(!is_valid_move(&b, sq("d0"), sq("e0"), PieceSide::Red));
+
+        let screened = board("4k4/9/9/9/4P4/9/9/9/9/4K4");
+        assert!(!is_valid_move(
+            &screened,
+            sq("e5"),
+            sq("d5"),
+            PieceSide::Red
+        ));
+        assert!(is_valid_move(&screened, sq("e5"), sq("e6"), PieceSide::Red));
+    }
+
+    #[test]
+    fn moves_must_not_leave_general_in_check() {
+        let b = board("3k5/9/9/9/9/9/4r4/9/4N4/4K4");
+        assert!(!is_valid_move(&b, sq("e1"), sq("d3"), PieceSide::Red));
+        assert!(is_in_check(
+            &board("3k5/9/9/9/9/9/4r4/9/9/4K4"),
+            PieceSide::Red
+        ));
+    }
+
+    #[test]
+    fn detects_checkmate_and_stalemate() {
+        let mate = board("3k5/3R5/3R5/9/9/9/9/9/9/4K4");
+        assert!(is_checkmate(&mate, PieceSide::Black));
+        assert!(!is_stalemate(&mate, PieceSide::Black));
+
+        let stalemate = board("3k5/R8/9/9/9/9/9/9/9/4K4");
+        assert!(is_stalemate(&stalemate, PieceSide::Black));
+        assert!(!is_checkmate(&stalemate, PieceSide::Black));
+    }
+
+    #[test]
+    fn validates_piece_placement() {
+        let start =
+            parse_placement("rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR").unwrap();
+        assert!(validate_position(&start, PieceSide::Red).is_empty());
+
+        // Advisor off the palace diagonals, elephant off its points, pawn on an odd file
+        // before crossing the river, and a third chariot.
+        let problems = validate_position(
+            &board("3k5/4a4/9/9/9/9/1P7/3B5/3A5/R2K1R2R"),
+            PieceSide::Red,
+        );
+        let squares: Vec<Position> = problems
+            .iter()
+            .filter_map(|p| match p {
+                PositionProblem::IllegalSquare { pos, .. } => Some(*pos),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(squares, [(6, 1), (7, 3), (8, 3)]);
+        assert!(problems.contains(&PositionProblem::TooManyPieces {
+            side: PieceSide::Red,
+            kind: PieceKind::Ju,
+            count: 3,
+        }));
+
+        assert_eq!(
+            validate_position(&board("4k4/9/9/9/9/9/9/9/9/4K4"), PieceSide::Red),
+            [PositionProblem::GeneralsFacing]
+        );
+        assert!(validate_position(&board("4k4/9/9/9/9/9/9/9/9/3K4R"), PieceSide::Black).is_empty());
+        assert_eq!(
+            validate_position(&board("4k3R/9/9/9/9/9/9/9/9/3K5"), PieceSide::Red),
+            [PositionProblem::OpponentInCheck]
+        );
+        assert_eq!(
+            validate_position(&board("9/9/9/9/9/9/9/9/9/3K5"), PieceSide::Red),
+            [PositionProblem::MissingGeneral(PieceSide::Black)]
+        );
+    }
+
+    #[test]
+    fn explains_illegal_moves() {
+        let reason = |placement: &str, mv: &str| {
+            check_move(
+                &board(placement),
+                sq(&mv[..2]),
+                sq(&mv[2..]),
+                PieceSide::Red,
+            )
+            .err()
+        };
+
+        let start = "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR";
+        assert_eq!(reason(start, "b0c2"), None);
+        assert_eq!(reason(start, "b0d1"), Some(IllegalMove::HorseLegBlocked));
+        assert_eq!(
+            reason(start, "b0a1"),
+            Some(IllegalMove::WrongShape(PieceKind::Ma))
+        );
+        assert_eq!(reason(start, "h2h8"), Some(IllegalMove::PathBlocked));
+        assert_eq!(reason(start, "b2b7"), Some(IllegalMove::NoCannonScreen));
+        assert_eq!(reason(start, "a3b3"), Some(IllegalMove::PawnSideways));
+        assert_eq!(
+            reason(start, "d0d1"),
+            Some(IllegalMove::WrongShape(PieceKind::Shi))
+        );
+        assert_eq!(reason(start, "a0a3"), Some(IllegalMove::CaptureOwnPiece));
+        assert_eq!(reason(start, "a5a6"), Some(IllegalMove::NoPiece));
+        assert_eq!(reason(start, "a9a8"), Some(IllegalMove::NotYourPiece));
+
+        let screens = "4k4/4r4/9/4p4/4p4/9/9/4C4/9/4K4";
+        assert_eq!(reason(screens, "e2e8"), Some(IllegalMove::TooManyScreens));
+
+        let pawn = "4k4/9/9/9/4P4/9/9/9/9/2B1K4";
+        assert_eq!(reason(pawn, "c0e2"), None);
+        assert_eq!(reason(pawn, "e5e4"), Some(IllegalMove::PawnBackward));
+        assert_eq!(reason(pawn, "e5d5"), Some(IllegalMove::FlyingGeneral));
+
+        let check = "3k5/9/9/9/9/9/9/4B4/9/4K2r1";
+        assert_eq!(reason(check, "e0e1"), None);
+        assert_eq!(
+            reason(check, "e2c0"),
+            Some(IllegalMove::LeavesGeneralInCheck)
+        );
+        assert_eq!(
+            reason(check, "e0f0"),
+            Some(IllegalMove::LeavesGeneralInCheck)
+        );
+        assert_eq!(reason(check, "e0d0"), Some(IllegalMove::FlyingGeneral));
+        assert_eq!(
+            reason(check, "e0g0"),
+            Some(IllegalMove::WrongShape(PieceKind::Jiang))
+        );
+        assert_eq!(
+            reason(check, "e2e4"),
+            Some(IllegalMove::WrongShape(PieceKind::Xiang))
+        );
+    }
 }
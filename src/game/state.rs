@@ -1,7 +1,15 @@
 use crate::game::{
     board::{self, BoardState},
+    fen::{self, FenError},
+    notation::{self, Notation, NotationError},
     piece::{Piece, PieceSide},
-    rules::{is_checkmate, is_in_check, is_stalemate, is_valid_move},
+    record::GameMetadata,
+    rules::{
+        chased_pieces, has_insufficient_material, is_chased, is_checkmate, is_in_check,
+        is_stalemate, is_valid_move, validate_position,
+    },
+    tree::{GameTree, NodeId, ROOT},
+    zobrist,
 };
 use serde::{Deserialize, Serialize};
 
@@ -12,25 +20,132 @@ pub enum MoveResult {
     Capture(Piece),
     Check,
     CaptureAndCheck(Piece),
-    Checkmate(PieceSide),
-    Stalemate(PieceSide),
+    /// The move ended the game with the given status.
+    GameOver(GameStatus),
+}
+
+/// Why a game ended.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum GameEndReason {
+    Checkmate,
+    /// The side to move has no legal moves and loses.
+    Stalemate,
+    /// The loser repeated the position by checking perpetually.
+    PerpetualCheck,
+    /// The loser repeated the position by chasing a piece perpetually.
+    PerpetualChase,
+    /// The position repeated without either side violating the rules.
+    Repetition,
+    /// The no-capture move limit was reached.
+    MoveLimit,
+    /// Neither side has material left to checkmate.
+    InsufficientMaterial,
+    Resignation,
+    Timeout,
+    Agreement,
+    /// The result was decided outside the rules, e.g. recorded in an imported game.
+    Adjudication,
+}
+
+impl GameEndReason {
+    /// Returns the Chinese description of this reason.
+    pub fn label(&self) -> &'static str {
+        match self {
+            GameEndReason::Checkmate => "将死",
+            GameEndReason::Stalemate => "困毙",
+            GameEndReason::PerpetualCheck => "长将",
+            GameEndReason::PerpetualChase => "长捉",
+            GameEndReason::Repetition => "重复局面",
+            GameEndReason::MoveLimit => "自然限着",
+            GameEndReason::InsufficientMaterial => "子力不足",
+            GameEndReason::Resignation => "认输",
+            GameEndReason::Timeout => "超时",
+            GameEndReason::Agreement => "议和",
+            GameEndReason::Adjudication => "裁定",
+        }
+    }
+}
+
+/// Default number of full moves without a capture before the game is drawn.
+pub const DEFAULT_MOVE_LIMIT: u32 = 60;
+
+/// Number of times a position may occur before the repetition is adjudicated.
+const REPETITION_LIMIT: usize = 3;
+
+/// Number of times a position must occur before a draw by repetition can be claimed. The
+/// rules only adjudicate at [`REPETITION_LIMIT`], but once a position recurs with neither
+/// side offending, the cycle can only end in a draw, so players may stop it a cycle early.
+const CLAIM_REPETITION_LIMIT: usize = 2;
+
+/// Per-ply bookkeeping used to adjudicate repeated positions.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlyRecord {
+    /// Zobrist hash of the position reached.
+    pub key: u64,
+    /// Whether the move that reached this position gave check.
+    pub check: bool,
+    /// Whether the move that reached this position started chasing a piece.
+    pub chase: bool,
+}
+
+impl PlyRecord {
+    fn new(key: u64) -> Self {
+        Self {
+            key,
+            check: false,
+            chase: false,
+        }
+    }
 }
 
 /// Represents a single move in the game history.
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Move {
     pub from: (usize, usize),
     pub to: (usize, usize),
     pub captured: Option<Piece>,
 }
 
-#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize, Default)]
 pub enum GameStatus {
     #[default]
     InProgress,
-    RedWins,
-    BlackWins,
-    Draw,
+    RedWins(GameEndReason),
+    BlackWins(GameEndReason),
+    Draw(GameEndReason),
+}
+
+impl GameStatus {
+    /// Returns the status for a win by the given side.
+    pub fn win(winner: PieceSide, reason: GameEndReason) -> Self {
+        match winner {
+            PieceSide::Red => GameStatus::RedWins(reason),
+            PieceSide::Black => GameStatus::BlackWins(reason),
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        *self != GameStatus::InProgress
+    }
+
+    /// Returns the winning side, or None for a draw or an unfinished game.
+    pub fn winner(&self) -> Option<PieceSide> {
+        match self {
+            GameStatus::RedWins(_) => Some(PieceSide::Red),
+            GameStatus::BlackWins(_) => Some(PieceSide::Black),
+            GameStatus::InProgress | GameStatus::Draw(_) => None,
+        }
+    }
+
+    /// Returns why the game ended, or None if it is still in progress.
+    pub fn reason(&self) -> Option<GameEndReason> {
+        match self {
+            GameStatus::InProgress => None,
+            GameStatus::RedWins(reason)
+            | GameStatus::BlackWins(reason)
+            | GameStatus::Draw(reason) => Some(*reason),
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -38,7 +153,34 @@ pub struct GameState {
     pub board: BoardState,
     pub current_turn: PieceSide,
     pub status: GameStatus,
+    /// Moves leading from the start position to the current node of `tree`.
     pub move_history: Vec<Move>,
+    /// Every line played or loaded, including variations.
+    #[serde(default)]
+    pub tree: GameTree,
+    /// Node of the current position in `tree`.
+    #[serde(default)]
+    pub current: NodeId,
+    /// Plies since the last capture.
+    #[serde(default)]
+    pub halfmove_clock: u32,
+    /// Full move number, incremented after each Black move.
+    #[serde(default = "default_fullmove_number")]
+    pub fullmove_number: u32,
+    /// FEN of the position the game started from, or None for the standard opening.
+    #[serde(default)]
+    pub start_fen: Option<String>,
+    /// One record per position reached, starting with the root position.
+    #[serde(default)]
+    pub ply_records: Vec<PlyRecord>,
+    /// Zobrist hash of the board and side to move, updated incrementally.
+    #[serde(default)]
+    pub hash: u64,
+    /// Full moves without a capture before the game is drawn, or None to disable the rule.
+    #[serde(default = "default_move_limit")]
+    pub move_limit: Option<u32>,
+    #[serde(default)]
+    pub metadata: GameMetadata,
     #[serde(skip)]
     pub selected_piece: Option<(usize, usize)>,
     #[serde(skip)]
@@ -49,11 +191,22 @@ pub struct GameState {
 
 impl Default for GameState {
     fn default() -> Self {
+        let board = board::init_board();
+        let hash = zobrist::hash_position(&board, PieceSide::Red);
         Self {
-            board: board::init_board(),
+            ply_records: vec![PlyRecord::new(hash)],
+            hash,
+            board,
             current_turn: PieceSide::Red,
             status: GameStatus::InProgress,
             move_history: Vec::new(),
+            tree: GameTree::default(),
+            current: ROOT,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            start_fen: None,
+            move_limit: default_move_limit(),
+            metadata: GameMetadata::default(),
             selected_piece: None,
             valid_moves: Vec::new(),
             last_move: None,
@@ -61,10 +214,50 @@ impl Default for GameState {
     }
 }
 
+fn default_fullmove_number() -> u32 {
+    1
+}
+
+fn default_move_limit() -> Option<u32> {
+    Some(DEFAULT_MOVE_LIMIT)
+}
+
 impl GameState {
-    /// Returns true if it's Black's turn (AI's turn in Player vs AI mode).
-    pub fn is_ai_turn(&self) -> bool {
-        self.current_turn == PieceSide::Black
+    /// Creates a game starting from the given FEN position, rejecting positions that cannot
+    /// occur in a game.
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let parsed = fen::parse_fen(fen)?;
+        let problems = validate_position(&parsed.board, parsed.side);
+        if !problems.is_empty() {
+            return Err(FenError::IllegalPosition(problems));
+        }
+        let mut game = Self {
+            board: parsed.board,
+            current_turn: parsed.side,
+            halfmove_clock: parsed.halfmove_clock,
+            fullmove_number: parsed.fullmove_number,
+            ..Self::default()
+        };
+        game.start_fen = Some(game.to_fen());
+        game.hash = game.compute_hash();
+        game.ply_records = vec![PlyRecord::new(game.hash)];
+        game.update_game_status();
+        Ok(game)
+    }
+
+    /// Returns the FEN of the current position.
+    pub fn to_fen(&self) -> String {
+        fen::to_fen(
+            &self.board,
+            self.current_turn,
+            self.halfmove_clock,
+            self.fullmove_number,
+        )
+    }
+
+    /// Computes the Zobrist hash of the current position from scratch.
+    pub fn compute_hash(&self) -> u64 {
+        zobrist::hash_position(&self.board, self.current_turn)
     }
 
     /// Converts a board position (row, col) to UCI coordinate format (e.g., "a0", "i9").
@@ -108,6 +301,59 @@ impl GameState {
         })
     }
 
+    /// Parses a move for the side to move, written in ICCS, Chinese or WXF notation.
+    pub fn parse_move(&self, text: &str) -> Result<Move, NotationError> {
+        notation::parse_move(&self.board, self.current_turn, text)
+    }
+
+    /// Formats a move that has not been made yet in the given notation.
+    pub fn format_move(&self, m: &Move, notation: Notation) -> Option<String> {
+        notation::format_move(&self.board, m, notation)
+    }
+
+    /// Returns the board as it was before the last move.
+    pub fn board_before_last_move(&self) -> Option<BoardState> {
+        let last = self.move_history.last()?;
+        let mut board = self.board;
+        board[last.from.0][last.from.1] = board[last.to.0][last.to.1];
+        board[last.to.0][last.to.1] = last.captured;
+        Some(board)
+    }
+
+    /// Returns the last move in the given notation.
+    pub fn last_move_notation(&self, notation: Notation) -> Option<String> {
+        let board = self.board_before_last_move()?;
+        notation::format_move(&board, self.move_history.last()?, notation)
+    }
+
+    /// Counts the leaf nodes of the legal move tree to the given depth, for testing move generation.
+    #[cfg(test)]
+    pub fn perft(&self, depth: u32) -> u64 {
+        use crate::game::rules::SearchBoard;
+
+        fn count(board: &mut SearchBoard, side: PieceSide, depth: u32) -> u64 {
+            let mut moves = Vec::new();
+            board.legal_moves(side, &mut moves);
+            if depth <= 1 {
+                return moves.len() as u64;
+            }
+            moves
+                .into_iter()
+                .map(|(from, to)| {
+                    let captured = board.make_move(from, to);
+                    let nodes = count(board, side.opponent(), depth - 1);
+                    board.unmake_move(from, to, captured);
+                    nodes
+                })
+                .sum()
+        }
+
+        if depth == 0 {
+            return 1;
+        }
+        count(&mut SearchBoard::new(&self.board), self.current_turn, depth)
+    }
+
     /// Returns the move history in UCI format for engine position command.
     pub fn moves_to_uci(&self) -> String {
         self.move_history
@@ -118,35 +364,74 @@ impl GameState {
     }
 
     pub fn make_move(&mut self, from: (usize, usize), to: (usize, usize)) -> MoveResult {
-        if self.status != GameStatus::InProgress {
+        if self.status.is_over() {
             return MoveResult::Invalid;
         }
+        self.play(from, to, true)
+    }
+
+    /// Plays a move read from a game record. Neither the end of the game nor the automatic
+    /// repetition and draw rules stop it, since the record tells how the game went on; only
+    /// checkmate and stalemate are detected. Returns false if the move is illegal.
+    pub fn replay_move(&mut self, from: (usize, usize), to: (usize, usize)) -> bool {
+        !matches!(self.play(from, to, false), MoveResult::Invalid)
+    }
+
+    /// Makes a move whether or not the game is over, so that navigation can step through
+    /// a line that was played before the game ended. `adjudicate` applies the repetition
+    /// and draw rules.
+    fn play(&mut self, from: (usize, usize), to: (usize, usize), adjudicate: bool) -> MoveResult {
         if !is_valid_move(&self.board, from, to, self.current_turn) {
             return MoveResult::Invalid;
         }
 
+        let board_before = self.board;
+
         let piece = self.board[from.0][from.1].take();
         let captured = self.board[to.0][to.1].take();
         self.board[to.0][to.1] = piece;
+        self.update_hash(from, to, piece, captured);
 
         let current_move = Move { from, to, captured };
+        self.current = match self.tree.child_with_move(self.current, from, to) {
+            Some(child) => child,
+            None => self.tree.add_child(self.current, current_move),
+        };
         self.move_history.push(current_move);
         self.last_move = Some(current_move);
 
         let moving_side = self.current_turn;
-        self.current_turn = match self.current_turn {
-            PieceSide::Red => PieceSide::Black,
-            PieceSide::Black => PieceSide::Red,
-        };
+        self.current_turn = moving_side.opponent();
+        if captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if moving_side == PieceSide::Black {
+            self.fullmove_number += 1;
+        }
 
-        let was_stalemate = self.update_game_status();
         let opponent_in_check = is_in_check(&self.board, self.current_turn);
-        if self.status == GameStatus::RedWins || self.status == GameStatus::BlackWins {
-            if was_stalemate {
-                MoveResult::Stalemate(moving_side)
-            } else {
-                MoveResult::Checkmate(moving_side)
+        let mut record = PlyRecord::new(self.hash);
+        record.check = opponent_in_check;
+        // Only pieces chased now are looked up on the board before the move.
+        record.chase = chased_pieces(&self.board, moving_side)
+            .into_iter()
+            .any(|pos| !is_chased(&board_before, pos, moving_side));
+        self.ply_records.push(record);
+
+        self.status = GameStatus::InProgress;
+        self.update_game_status();
+        if adjudicate && !self.status.is_over() {
+            if let Some(status) = self.adjudicate_repetition(REPETITION_LIMIT) {
+                self.status = status;
+            } else if let Some(reason) = self.natural_draw() {
+                self.status = GameStatus::Draw(reason);
             }
+        }
+
+        if self.status.is_over() {
+            MoveResult::GameOver(self.status)
         } else if let Some(captured_piece) = captured {
             if opponent_in_check {
                 MoveResult::CaptureAndCheck(captured_piece)
@@ -160,31 +445,153 @@ impl GameState {
         }
     }
 
-    fn update_game_status(&mut self) -> bool {
+    /// Sets the result of the game in the current position and records it in the tree, so
+    /// that navigating back to this position restores it.
+    pub fn set_result(&mut self, status: GameStatus) {
+        self.status = status;
+        self.tree.set_result(self.current, status);
+    }
+
+    /// Returns how the game ended: the status at the end of the main line, wherever the
+    /// board is while browsing.
+    pub fn final_status(&self) -> GameStatus {
+        let end = self.tree.main_line(ROOT).last().copied().unwrap_or(ROOT);
+        if end == self.current {
+            return self.status;
+        }
+        if let Some(result) = self.tree.node(end).result {
+            return result;
+        }
+        let mut game = self.clone();
+        game.go_to(end);
+        game.status
+    }
+
+    /// Ends the game as a loss by resignation for `side`.
+    pub fn resign(&mut self, side: PieceSide) {
+        if !self.status.is_over() {
+            self.set_result(GameStatus::win(side.opponent(), GameEndReason::Resignation));
+        }
+    }
+
+    /// Ends the game as a draw agreed by both sides.
+    pub fn agree_draw(&mut self) {
+        if !self.status.is_over() {
+            self.set_result(GameStatus::Draw(GameEndReason::Agreement));
+        }
+    }
+
+    /// Claims a draw before the rules declare one. The claim is upheld if the current position
+    /// has occurred before without either side checking or chasing perpetually, or if no
+    /// capture has been made for the default move limit while the rule is switched off.
+    /// Returns true if the game was drawn.
+    pub fn claim_draw(&mut self) -> bool {
+        if self.status.is_over() {
+            return false;
+        }
+        let limit = self.move_limit.unwrap_or(DEFAULT_MOVE_LIMIT);
+        let reason = if self.halfmove_clock >= limit * 2 {
+            GameEndReason::MoveLimit
+        } else if self.adjudicate_repetition(CLAIM_REPETITION_LIMIT)
+            == Some(GameStatus::Draw(GameEndReason::Repetition))
+        {
+            GameEndReason::Repetition
+        } else {
+            return false;
+        };
+        self.set_result(GameStatus::Draw(reason));
+        true
+    }
+
+    /// Ends the game as a loss on time for `side`.
+    pub fn lose_on_time(&mut self, side: PieceSide) {
+        if !self.status.is_over() {
+            self.set_result(GameStatus::win(side.opponent(), GameEndReason::Timeout));
+        }
+    }
+
+    fn update_game_status(&mut self) {
+        let winner = self.current_turn.opponent();
         if is_checkmate(&self.board, self.current_turn) {
-            self.status = match self.current_turn {
-                PieceSide::Red => GameStatus::BlackWins,
-                PieceSide::Black => GameStatus::RedWins,
-            };
-            false
+            self.status = GameStatus::win(winner, GameEndReason::Checkmate);
         } else if is_stalemate(&self.board, self.current_turn) {
-            self.status = match self.current_turn {
-                PieceSide::Red => GameStatus::BlackWins,
-                PieceSide::Black => GameStatus::RedWins,
-            };
-            true
-        } else {
-            false
+            self.status = GameStatus::win(winner, GameEndReason::Stalemate);
         }
     }
 
-    /// Undo the last move. Returns the undone move if successful.
-    pub fn undo_last_move(&mut self) -> Option<Move> {
-        if self.move_history.is_empty() {
+    /// Applies the Asian rules for repeated positions once the current position has occurred
+    /// `limit` times: a side whose every move in the cycle was a check (perpetual check) or a
+    /// check or chase (perpetual chase) loses, and any other repetition is a draw.
+    fn adjudicate_repetition(&self, limit: usize) -> Option<GameStatus> {
+        let last = self.ply_records.len().checked_sub(1)?;
+        let key = self.ply_records[last].key;
+        let occurrences: Vec<usize> = (0..=last)
+            .filter(|&i| self.ply_records[i].key == key)
+            .collect();
+        if occurrences.len() < limit {
             return None;
         }
+        let first = occurrences[occurrences.len() - limit];
+
+        // Records alternate movers backwards from the side that just moved.
+        let just_moved = self.current_turn.opponent();
+        let cycle = &self.ply_records[first + 1..=last];
+        let moves_by = |side: PieceSide| {
+            cycle.iter().rev().enumerate().filter(move |(i, _)| {
+                let mover = if i % 2 == 0 {
+                    just_moved
+                } else {
+                    just_moved.opponent()
+                };
+                mover == side
+            })
+        };
+        let offence = |side: PieceSide| {
+            if moves_by(side).all(|(_, r)| r.check) {
+                Some(GameEndReason::PerpetualCheck)
+            } else if moves_by(side).all(|(_, r)| r.check || r.chase) {
+                Some(GameEndReason::PerpetualChase)
+            } else {
+                None
+            }
+        };
+
+        // Perpetual check outranks perpetual chase when both sides offend.
+        let status = match (offence(PieceSide::Red), offence(PieceSide::Black)) {
+            (Some(reason), None) => GameStatus::BlackWins(reason),
+            (None, Some(reason)) => GameStatus::RedWins(reason),
+            (Some(GameEndReason::PerpetualCheck), Some(GameEndReason::PerpetualChase)) => {
+                GameStatus::BlackWins(GameEndReason::PerpetualCheck)
+            }
+            (Some(GameEndReason::PerpetualChase), Some(GameEndReason::PerpetualCheck)) => {
+                GameStatus::RedWins(GameEndReason::PerpetualCheck)
+            }
+            _ => GameStatus::Draw(GameEndReason::Repetition),
+        };
+        Some(status)
+    }
+
+    /// Checks the draw conditions that do not depend on the move history.
+    fn natural_draw(&self) -> Option<GameEndReason> {
+        if has_insufficient_material(&self.board) {
+            Some(GameEndReason::InsufficientMaterial)
+        } else if self
+            .move_limit
+            .is_some_and(|limit| self.halfmove_clock >= limit * 2)
+        {
+            Some(GameEndReason::MoveLimit)
+        } else {
+            None
+        }
+    }
 
-        let undone_move = self.move_history.pop().unwrap();
+    /// Steps back to the previous position, keeping the move in the tree.
+    /// Returns the undone move if successful.
+    pub fn undo_last_move(&mut self) -> Option<Move> {
+        let parent = self.tree.node(self.current).parent?;
+        let undone_move = self.move_history.pop()?;
+        self.current = parent;
+        self.ply_records.pop();
 
         let piece = self.board[undone_move.to.0][undone_move.to.1].take();
         self.board[undone_move.from.0][undone_move.from.1] = piece;
@@ -192,19 +599,135 @@ impl GameState {
         if let Some(captured) = undone_move.captured {
             self.board[undone_move.to.0][undone_move.to.1] = Some(captured);
         }
+        self.update_hash(
+            undone_move.from,
+            undone_move.to,
+            piece,
+            undone_move.captured,
+        );
 
         self.last_move = self.move_history.last().copied();
-        self.current_turn = match self.current_turn {
-            PieceSide::Red => PieceSide::Black,
-            PieceSide::Black => PieceSide::Red,
-        };
-        self.status = GameStatus::InProgress;
+        self.current_turn = self.current_turn.opponent();
+        if self.current_turn == PieceSide::Black {
+            self.fullmove_number = self.fullmove_number.saturating_sub(1).max(1);
+        }
+        self.halfmove_clock = self.recompute_halfmove_clock();
+        self.status = self
+            .tree
+            .node(parent)
+            .result
+            .unwrap_or(GameStatus::InProgress);
         self.selected_piece = None;
         self.valid_moves.clear();
 
         Some(undone_move)
     }
 
+    /// Toggles the hash for a piece moving between two squares, which is its own inverse.
+    fn update_hash(
+        &mut self,
+        from: (usize, usize),
+        to: (usize, usize),
+        piece: Option<Piece>,
+        captured: Option<Piece>,
+    ) {
+        if let Some(piece) = piece {
+            self.hash ^= zobrist::piece_key(piece, from) ^ zobrist::piece_key(piece, to);
+        }
+        if let Some(captured) = captured {
+            self.hash ^= zobrist::piece_key(captured, to);
+        }
+        self.hash ^= zobrist::side_key();
+    }
+
+    /// Counts plies since the last capture, falling back to the starting position's clock.
+    fn recompute_halfmove_clock(&self) -> u32 {
+        let since_capture = self
+            .move_history
+            .iter()
+            .rev()
+            .take_while(|m| m.captured.is_none())
+            .count() as u32;
+        if since_capture < self.move_history.len() as u32 {
+            return since_capture;
+        }
+        let start_clock = self
+            .start_fen
+            .as_deref()
+            .and_then(|f| fen::parse_fen(f).ok())
+            .map_or(0, |f| f.halfmove_clock);
+        start_clock + since_capture
+    }
+
+    /// Replays the main continuation's next move, undoing an undo. Returns the move if there
+    /// was one.
+    pub fn go_forward(&mut self) -> Option<Move> {
+        let &child = self.tree.node(self.current).children.first()?;
+        let m = self.tree.node(child).mv?;
+        self.step_to(child).then_some(m)
+    }
+
+    /// Plays the move leading to a child of the current node, restoring the result recorded
+    /// there. Returns false if the move cannot be played.
+    fn step_to(&mut self, child: NodeId) -> bool {
+        let Some(m) = self.tree.node(child).mv else {
+            return false;
+        };
+        if matches!(self.play(m.from, m.to, true), MoveResult::Invalid) {
+            return false;
+        }
+        if let Some(result) = self.tree.node(self.current).result {
+            self.status = result;
+        }
+        true
+    }
+
+    /// Moves to the end of the main continuation of the current position.
+    pub fn go_to_end(&mut self) -> bool {
+        let end = self
+            .tree
+            .main_line(self.current)
+            .last()
+            .copied()
+            .unwrap_or(self.current);
+        self.go_to(end)
+    }
+
+    /// Moves to any node of the tree by stepping back to the common ancestor and replaying
+    /// forward from there. Returns false if the node does not exist.
+    pub fn go_to(&mut self, target: NodeId) -> bool {
+        if !self.tree.contains(target) {
+            return false;
+        }
+        while !self.tree.is_ancestor(self.current, target) {
+            self.undo_last_move();
+        }
+        let path = self.tree.path(target);
+        let start = path.iter().position(|&id| id == self.current).unwrap_or(0);
+        for &id in &path[start + 1..] {
+            if !self.step_to(id) {
+                return false;
+            }
+        }
+        self.selected_piece = None;
+        self.valid_moves.clear();
+        true
+    }
+
+    /// Removes a node and every move after it, first stepping back out of the branch if the
+    /// current position is inside it. The root cannot be deleted.
+    pub fn delete_branch(&mut self, id: NodeId) -> bool {
+        let Some(parent) = self.tree.node(id).parent else {
+            return false;
+        };
+        if self.tree.is_ancestor(id, self.current) && !self.go_to(parent) {
+            return false;
+        }
+        let remap = self.tree.delete(id);
+        self.current = remap[self.current].unwrap_or(ROOT);
+        true
+    }
+
     /// Undo the last two moves. Returns the undone moves if successful.
     pub fn undo_last_two_moves(&mut self) -> Option<(Move, Move)> {
         if self.move_history.len() < 2 {
@@ -217,3 +740,345 @@ impl GameState {
         Some((move1, move2))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(game: &mut GameState, moves: &[&str]) {
+        for uci in moves {
+            let m = GameState::uci_to_move(uci).unwrap();
+            assert!(
+                !matches!(game.make_move(m.from, m.to), MoveResult::Invalid),
+                "{}",
+                uci
+            );
+        }
+    }
+
+    #[test]
+    fn incremental_hash_matches_recomputed_hash() {
+        let mut game = GameState::default();
+        assert_eq!(game.hash, game.compute_hash());
+
+        play(&mut game, &["h2e2", "h9g7", "b2b9", "a9b9", "h0g2"]);
+        assert_eq!(game.hash, game.compute_hash());
+
+        while game.undo_last_move().is_some() {
+            assert_eq!(game.hash, game.compute_hash());
+        }
+        assert_eq!(game.hash, GameState::default().hash);
+    }
+
+    #[test]
+    fn hash_depends_on_side_to_move() {
+        let red = GameState::from_fen("4k4/9/9/9/9/9/9/9/9/3K5 w - - 0 1").unwrap();
+        let black = GameState::from_fen("4k4/9/9/9/9/9/9/9/9/3K5 b - - 0 1").unwrap();
+        assert_ne!(red.hash, black.hash);
+    }
+
+    #[test]
+    fn transpositions_share_a_hash() {
+        let mut a = GameState::default();
+        let mut b = GameState::default();
+        play(&mut a, &["h2e2", "h9g7", "b0c2"]);
+        play(&mut b, &["b0c2", "h9g7", "h2e2"]);
+        assert_eq!(a.hash, b.hash);
+    }
+
+    #[test]
+    fn games_can_be_resigned_or_drawn() {
+        let mut game = GameState::default();
+        play(&mut game, &["h0g2", "h9g7"]);
+        assert!(!game.claim_draw());
+        play(&mut game, &["g2h0", "g7h9"]);
+        assert!(game.claim_draw());
+        assert_eq!(game.status, GameStatus::Draw(GameEndReason::Repetition));
+
+        let mut game = GameState::from_fen("4k4/9/9/9/9/9/9/9/9/3K5 w - - 120 80").unwrap();
+        game.move_limit = None;
+        assert!(game.claim_draw());
+        assert_eq!(game.status, GameStatus::Draw(GameEndReason::MoveLimit));
+
+        let mut game = GameState::default();
+        game.resign(PieceSide::Red);
+        assert_eq!(
+            game.status,
+            GameStatus::BlackWins(GameEndReason::Resignation)
+        );
+        game.agree_draw();
+        assert_eq!(
+            game.status,
+            GameStatus::BlackWins(GameEndReason::Resignation)
+        );
+    }
+
+    #[test]
+    fn flag_fall_loses_on_time() {
+        let mut game = GameState::default();
+        play(&mut game, &["h2e2"]);
+        game.lose_on_time(PieceSide::Black);
+        assert_eq!(game.status, GameStatus::RedWins(GameEndReason::Timeout));
+        assert!(matches!(
+            game.make_move((0, 7), (2, 6)),
+            MoveResult::Invalid
+        ));
+
+        game.lose_on_time(PieceSide::Red);
+        assert_eq!(game.status, GameStatus::RedWins(GameEndReason::Timeout));
+        game.undo_last_move();
+        assert_eq!(game.status, GameStatus::InProgress);
+        game.go_forward();
+        assert_eq!(game.status, GameStatus::RedWins(GameEndReason::Timeout));
+    }
+
+    #[test]
+    fn perpetual_check_loses() {
+        let mut game = GameState::from_fen("4k4/9/9/9/9/9/9/9/9/R2K5 w - - 0 1").unwrap();
+        play(
+            &mut game,
+            &[
+                "a0a9", "e9e8", "a9a8", "e8e9", "a8a9", "e9e8", "a9a8", "e8e9",
+            ],
+        );
+        assert_eq!(game.status, GameStatus::InProgress);
+        play(&mut game, &["a8a9"]);
+        assert_eq!(
+            game.status,
+            GameStatus::BlackWins(GameEndReason::PerpetualCheck)
+        );
+    }
+
+    #[test]
+    fn perpetual_chase_loses() {
+        // The chariot keeps attacking the unprotected cannon.
+        let mut game = GameState::from_fen("4k4/9/9/9/c8/9/9/9/9/1R1K5 w - - 0 1").unwrap();
+        play(
+            &mut game,
+            &["b0a0", "a5b5", "a0b0", "b5a5", "b0a0", "a5b5", "a0b0"],
+        );
+        assert_eq!(game.status, GameStatus::InProgress);
+        play(&mut game, &["b5a5"]);
+        assert_eq!(
+            game.status,
+            GameStatus::BlackWins(GameEndReason::PerpetualChase)
+        );
+    }
+
+    #[test]
+    fn perpetual_check_outranks_perpetual_chase() {
+        // Positions A B C D A B C D A, the last move by Red.
+        let cycle = |red: fn(&mut PlyRecord), black: fn(&mut PlyRecord)| {
+            let ply_records = [1, 2, 3, 4, 1, 2, 3, 4, 1]
+                .into_iter()
+                .enumerate()
+                .map(|(ply, key)| {
+                    let mut record = PlyRecord::new(key);
+                    match ply {
+                        0 => {}
+                        _ if ply % 2 == 0 => red(&mut record),
+                        _ => black(&mut record),
+                    }
+                    record
+                })
+                .collect();
+            let game = GameState {
+                current_turn: PieceSide::Black,
+                ply_records,
+                ..GameState::default()
+            };
+            game.adjudicate_repetition(REPETITION_LIMIT)
+        };
+        let check = |r: &mut PlyRecord| r.check = true;
+        let chase = |r: &mut PlyRecord| r.chase = true;
+        let idle = |_: &mut PlyRecord| {};
+
+        assert_eq!(
+            cycle(check, chase),
+            Some(GameStatus::BlackWins(GameEndReason::PerpetualCheck))
+        );
+        assert_eq!(
+            cycle(chase, check),
+            Some(GameStatus::RedWins(GameEndReason::PerpetualCheck))
+        );
+        assert_eq!(
+            cycle(idle, chase),
+            Some(GameStatus::RedWins(GameEndReason::PerpetualChase))
+        );
+        assert_eq!(
+            cycle(check, check),
+            Some(GameStatus::Draw(GameEndReason::Repetition))
+        );
+        assert_eq!(
+            cycle(chase, chase),
+            Some(GameStatus::Draw(GameEndReason::Repetition))
+        );
+    }
+
+    #[test]
+    fn mutual_repetition_is_a_draw() {
+        let mut game = GameState::default();
+        play(
+            &mut game,
+            &["h0g2", "h9g7", "g2h0", "g7h9", "h0g2", "h9g7", "g2h0"],
+        );
+        assert_eq!(game.status, GameStatus::InProgress);
+        play(&mut game, &["g7h9"]);
+        assert_eq!(game.status, GameStatus::Draw(GameEndReason::Repetition));
+    }
+
+    #[test]
+    fn pawn_attacks_are_not_chases() {
+        // Each pawn move attacks a chariot, which does not count as a chase.
+        let mut game = GameState::from_fen("3ak4/9/3rr4/4P4/9/9/9/9/9/5K3 w - - 0 1").unwrap();
+        play(
+            &mut game,
+            &["e6d6", "d9e8", "d6e6", "e8d9", "e6d6", "d9e8", "d6e6"],
+        );
+        assert!(!game.ply_records.iter().any(|r| r.chase));
+        play(&mut game, &["e8d9"]);
+        assert_eq!(game.status, GameStatus::Draw(GameEndReason::Repetition));
+    }
+
+    #[test]
+    fn bare_defences_are_a_draw() {
+        // Advisors and elephants cannot cross the river.
+        let mut game = GameState::from_fen("3k5/9/9/9/9/9/9/4B4/9/3AK4 w - - 0 1").unwrap();
+        play(&mut game, &["e2c4"]);
+        assert_eq!(
+            game.status,
+            GameStatus::Draw(GameEndReason::InsufficientMaterial)
+        );
+
+        let mut game = GameState::from_fen("3k5/9/9/9/9/4P4/9/9/9/4K4 w - - 0 1").unwrap();
+        play(&mut game, &["e4e5"]);
+        assert_eq!(game.status, GameStatus::InProgress);
+    }
+
+    #[test]
+    fn move_limit_draws_automatically() {
+        let fen = "4k4/9/9/9/9/9/9/9/9/R2K5 w - - 19 20";
+        let mut game = GameState::from_fen(fen).unwrap();
+        game.move_limit = Some(10);
+        play(&mut game, &["a0a1"]);
+        assert_eq!(game.status, GameStatus::Draw(GameEndReason::MoveLimit));
+
+        let mut game = GameState::from_fen(fen).unwrap();
+        game.move_limit = None;
+        play(&mut game, &["a0a1"]);
+        assert_eq!(game.status, GameStatus::InProgress);
+        assert_eq!(game.halfmove_clock, 20);
+    }
+
+    #[test]
+    fn navigation_restores_recorded_results() {
+        let mut game = GameState::default();
+        play(&mut game, &["h2e2", "h9g7", "h0g2"]);
+        game.undo_last_move();
+        game.undo_last_move();
+        let resigned = game.current;
+        game.resign(PieceSide::Black);
+
+        // The game being over does not stop stepping through the moves already played.
+        assert!(game.go_forward().is_some());
+        assert_eq!(game.status, GameStatus::InProgress);
+        assert!(game.go_to_end());
+        assert_eq!(game.moves_to_uci(), "h2e2 h9g7 h0g2");
+
+        assert!(game.go_to(resigned));
+        assert_eq!(game.status, GameStatus::RedWins(GameEndReason::Resignation));
+        assert!(game.go_to(ROOT));
+        assert_eq!(game.status, GameStatus::InProgress);
+        assert!(game.go_forward().is_some());
+        assert_eq!(game.status, GameStatus::RedWins(GameEndReason::Resignation));
+        assert!(matches!(
+            game.make_move((6, 0), (5, 0)),
+            MoveResult::Invalid
+        ));
+    }
+
+    #[test]
+    fn redo_replays_undone_moves() {
+        let mut game = GameState::default();
+        play(&mut game, &["h2e2", "h9g7", "h0g2"]);
+        let end_hash = game.hash;
+        game.undo_last_two_moves();
+        assert_eq!(game.moves_to_uci(), "h2e2");
+
+        assert_eq!(
+            game.go_forward().map(|m| GameState::move_to_uci(&m)),
+            Some("h9g7".to_string())
+        );
+        assert_eq!(game.current_turn, PieceSide::Red);
+        assert!(game.go_forward().is_some());
+        assert_eq!(game.hash, end_hash);
+        assert_eq!(game.go_forward(), None);
+    }
+
+    #[test]
+    fn first_and_last_follow_the_main_line() {
+        let mut game = GameState::default();
+        play(&mut game, &["h2e2", "h9g7", "h0g2"]);
+        let end = game.current;
+        game.undo_last_move();
+        game.undo_last_move();
+        play(&mut game, &["b9c7"]);
+
+        assert!(game.go_to(ROOT));
+        assert!(game.move_history.is_empty());
+        assert_eq!(game.board, GameState::default().board);
+        assert_eq!(game.hash, GameState::default().hash);
+
+        assert!(game.go_to_end());
+        assert_eq!(game.current, end);
+        assert_eq!(game.moves_to_uci(), "h2e2 h9g7 h0g2");
+        assert!(game.go_to_end());
+        assert_eq!(game.current, end);
+    }
+
+    #[test]
+    fn navigation_keeps_every_line() {
+        let mut game = GameState::default();
+        play(&mut game, &["h2e2", "h9g7", "h0g2"]);
+        let main_end = game.current;
+        game.undo_last_move();
+        game.undo_last_move();
+        play(&mut game, &["b9c7"]);
+        let variation = game.current;
+        assert_eq!(game.tree.variation_index(variation), 1);
+
+        assert!(game.go_to(main_end));
+        assert_eq!(game.moves_to_uci(), "h2e2 h9g7 h0g2");
+        assert_eq!(game.hash, game.compute_hash());
+        assert_eq!(game.current_turn, PieceSide::Black);
+
+        assert!(game.go_to(variation));
+        assert_eq!(game.moves_to_uci(), "h2e2 b9c7");
+
+        assert!(game.go_to(ROOT));
+        assert_eq!(
+            game.go_forward()
+                .map(|m| GameState::move_to_uci(&m))
+                .as_deref(),
+            Some("h2e2")
+        );
+        assert!(game.go_to_end());
+        assert_eq!(game.current, main_end);
+        assert!(game.go_forward().is_none());
+        assert!(game.go_to(variation));
+
+        // Replaying a known move follows the existing node instead of adding one.
+        game.undo_last_move();
+        play(&mut game, &["h9g7"]);
+        assert_eq!(game.tree.node(game.current).children.len(), 1);
+
+        assert!(game.delete_branch(game.current));
+        assert_eq!(game.moves_to_uci(), "h2e2");
+        assert_eq!(game.tree.node(game.current).children.len(), 1);
+        assert_eq!(game.board, {
+            let mut board = GameState::default();
+            play(&mut board, &["h2e2"]);
+            board.board
+        });
+    }
+}
@@ -0,0 +1,232 @@
+//! Variation tree of a game.
+//!
+//! Every position reached is a node; the moves played from it are its children. The first
+//! child continues the main line and the others are variations, in order of preference.
+
+use crate::game::state::{GameStatus, Move};
+use serde::{Deserialize, Serialize};
+
+/// Index of a node in a [`GameTree`].
+pub type NodeId = usize;
+
+/// The node of the starting position.
+pub const ROOT: NodeId = 0;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct GameNode {
+    /// Move leading to this node, or None for the root.
+    pub mv: Option<Move>,
+    pub parent: Option<NodeId>,
+    /// Continuations from this position, the main line first.
+    pub children: Vec<NodeId>,
+    pub comment: Option<String>,
+    /// Result decided in this position by something other than the moves, such as a
+    /// resignation, restored when the position is navigated back to.
+    #[serde(default)]
+    pub result: Option<GameStatus>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameTree {
+    nodes: Vec<GameNode>,
+}
+
+impl Default for GameTree {
+    fn default() -> Self {
+        Self {
+            nodes: vec![GameNode::default()],
+        }
+    }
+}
+
+impl GameTree {
+    pub fn node(&self, id: NodeId) -> &GameNode {
+        &self.nodes[id]
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        id < self.nodes.len()
+    }
+
+    /// Returns the child of `id` reached by moving `from` to `to`.
+    pub fn child_with_move(
+        &self,
+        id: NodeId,
+        from: (usize, usize),
+        to: (usize, usize),
+    ) -> Option<NodeId> {
+        self.nodes[id].children.iter().copied().find(|&child| {
+            self.nodes[child]
+                .mv
+                .is_some_and(|m| m.from == from && m.to == to)
+        })
+    }
+
+    /// Adds a move after `parent` as its last variation, or as its main line if it has none.
+    pub fn add_child(&mut self, parent: NodeId, mv: Move) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(GameNode {
+            mv: Some(mv),
+            parent: Some(parent),
+            children: Vec::new(),
+            comment: None,
+            result: None,
+        });
+        self.nodes[parent].children.push(id);
+        id
+    }
+
+    /// Returns the nodes from the root down to `id`, inclusive.
+    pub fn path(&self, id: NodeId) -> Vec<NodeId> {
+        let mut path = vec![id];
+        let mut node = id;
+        while let Some(parent) = self.nodes[node].parent {
+            path.push(parent);
+            node = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Returns the nodes after `id` along the main continuation.
+    pub fn main_line(&self, id: NodeId) -> Vec<NodeId> {
+        let mut line = Vec::new();
+        let mut node = id;
+        while let Some(&child) = self.nodes[node].children.first() {
+            line.push(child);
+            node = child;
+        }
+        line
+    }
+
+    /// Returns true if `ancestor` lies on the path from the root to `id`, or is `id` itself.
+    pub fn is_ancestor(&self, ancestor: NodeId, id: NodeId) -> bool {
+        let mut node = Some(id);
+        while let Some(current) = node {
+            if current == ancestor {
+                return true;
+            }
+            node = self.nodes[current].parent;
+        }
+        false
+    }
+
+    /// Returns the position of `id` among its siblings, 0 being the main line.
+    pub fn variation_index(&self, id: NodeId) -> usize {
+        self.nodes[id].parent.map_or(0, |parent| {
+            self.nodes[parent]
+                .children
+                .iter()
+                .position(|&child| child == id)
+                .unwrap_or(0)
+        })
+    }
+
+    /// Moves a variation one place towards the main line. Returns false if it already is.
+    pub fn promote(&mut self, id: NodeId) -> bool {
+        let index = self.variation_index(id);
+        let Some(parent) = self.nodes[id].parent else {
+            return false;
+        };
+        if index == 0 {
+            return false;
+        }
+        self.nodes[parent].children.swap(index - 1, index);
+        true
+    }
+
+    /// Moves a variation one place away from the main line. Returns false if it is the last.
+    pub fn demote(&mut self, id: NodeId) -> bool {
+        let index = self.variation_index(id);
+        let Some(parent) = self.nodes[id].parent else {
+            return false;
+        };
+        if index + 1 >= self.nodes[parent].children.len() {
+            return false;
+        }
+        self.nodes[parent].children.swap(index, index + 1);
+        true
+    }
+
+    pub fn set_comment(&mut self, id: NodeId, comment: Option<String>) {
+        self.nodes[id].comment = comment.filter(|c| !c.trim().is_empty());
+    }
+
+    /// Records the result decided in a position, or forgets it if the game goes on.
+    pub fn set_result(&mut self, id: NodeId, result: GameStatus) {
+        self.nodes[id].result = result.is_over().then_some(result);
+    }
+
+    /// Removes `id` and everything after it. The remaining nodes are renumbered; the returned
+    /// table maps each old id to its new one, or None if it was removed.
+    pub fn delete(&mut self, id: NodeId) -> Vec<Option<NodeId>> {
+        let mut remap: Vec<Option<NodeId>> = (0..self.nodes.len()).map(Some).collect();
+        let Some(parent) = self.nodes[id].parent else {
+            return remap;
+        };
+        self.nodes[parent].children.retain(|&child| child != id);
+
+        let mut stack = vec![id];
+        while let Some(node) = stack.pop() {
+            remap[node] = None;
+            stack.extend(&self.nodes[node].children);
+        }
+
+        for (next, slot) in remap.iter_mut().flatten().enumerate() {
+            *slot = next;
+        }
+        let mut index = 0;
+        self.nodes.retain(|_| {
+            index += 1;
+            remap[index - 1].is_some()
+        });
+        for node in &mut self.nodes {
+            node.parent = node.parent.and_then(|p| remap[p]);
+            for child in &mut node.children {
+                *child = remap[*child].expect("children of kept nodes are kept");
+            }
+        }
+        remap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mv(from: (usize, usize), to: (usize, usize)) -> Move {
+        Move {
+            from,
+            to,
+            captured: None,
+        }
+    }
+
+    #[test]
+    fn variations_can_be_reordered_and_deleted() {
+        let mut tree = GameTree::default();
+        let a = tree.add_child(ROOT, mv((7, 7), (7, 4)));
+        let b = tree.add_child(ROOT, mv((7, 1), (7, 4)));
+        let a2 = tree.add_child(a, mv((0, 7), (2, 6)));
+        let b2 = tree.add_child(b, mv((0, 1), (2, 2)));
+        assert_eq!(tree.child_with_move(ROOT, (7, 1), (7, 4)), Some(b));
+        assert!(tree.is_ancestor(b, b2));
+        assert!(!tree.is_ancestor(a, b2));
+
+        assert!(tree.promote(b));
+        assert!(!tree.promote(b));
+        assert_eq!(tree.node(ROOT).children, vec![b, a]);
+        assert_eq!(tree.main_line(ROOT), vec![b, b2]);
+        assert!(tree.demote(b));
+        assert_eq!(tree.node(ROOT).children, vec![a, b]);
+
+        let remap = tree.delete(a);
+        assert_eq!(remap[a], None);
+        assert_eq!(remap[a2], None);
+        let b = remap[b].unwrap();
+        let b2 = remap[b2].unwrap();
+        assert_eq!(tree.node(ROOT).children, vec![b]);
+        assert_eq!(tree.path(b2), vec![ROOT, b, b2]);
+        assert_eq!(tree.node(b2).mv, Some(mv((0, 1), (2, 2))));
+    }
+}
@@ -0,0 +1,499 @@
+//! XQF, the binary game format of 象棋演播室 (XQStudio).
+//!
+//! A file starts with a 1024-byte header holding the version, the encryption keys, the
+//! squares of all 32 pieces and the game information. The move tree follows as a
+//! depth-first list of 4-byte records (from, to, flags, reserved), each optionally
+//! followed by a comment. The first record is the root and carries the comment shown
+//! before the first move. Files from version 11 on are encrypted with keys derived from
+//! the header.
+//!
+//! Text fields are stored in the encoding of the program that wrote them, usually GBK.
+//! They are decoded as GBK unless they are valid UTF-8.
+
+use crate::game::{
+    board::BoardState,
+    fen,
+    notation::NotationError,
+    pgn::{PgnError, PgnGame, PgnMove},
+    piece::{Piece, PieceSide},
+    state::GameState,
+};
+
+/// File extension of XQF files.
+pub const XQF_EXTENSION: &str = "xqf";
+
+const HEADER_SIZE: usize = 1024;
+
+/// Piece in each of the 32 piece slots, in FEN letters. DhtmlXQ uses the same order.
+pub const PIECE_SLOTS: &[u8; 32] = b"RNBAKABNRCCPPPPPrnbakabnrccppppp";
+
+/// Plain text the byte-stream key is derived from.
+const KEY_STREAM: &[u8; 32] = b"[(C) Copyright Mr. Dong Shiwei.]";
+
+/// Errors that can occur when reading an XQF file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum XqfError {
+    NotXqf,
+    Truncated,
+    InvalidPosition(String),
+    /// A move that cannot be played, with its one-based record number.
+    IllegalMove {
+        record: usize,
+        error: NotationError,
+    },
+}
+
+impl std::fmt::Display for XqfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XqfError::NotXqf => write!(f, "Not an XQF file"),
+            XqfError::Truncated => write!(f, "XQF file is truncated"),
+            XqfError::InvalidPosition(e) => write!(f, "Invalid start position: {}", e),
+            XqfError::IllegalMove { record, error } => write!(f, "Record {}: {}", record, error),
+        }
+    }
+}
+
+impl std::error::Error for XqfError {}
+
+/// Decryption keys of a file; all zero before version 11.
+struct Keys {
+    xy: u8,
+    xyf: u8,
+    xyt: u8,
+    /// Added to every comment length.
+    comment_size: u32,
+    stream: [u8; 32],
+}
+
+/// The key schedule's `x * x * 54 + 221`, truncated to a byte.
+fn square54_plus221(x: u8) -> u8 {
+    (u32::from(x) * u32::from(x) * 54 + 221) as u8
+}
+
+impl Keys {
+    fn new(header: &[u8]) -> Self {
+        let version = header[2];
+        if version <= 10 {
+            return Self {
+                xy: 0,
+                xyf: 0,
+                xyt: 0,
+                comment_size: 0,
+                stream: [0; 32],
+            };
+        }
+
+        let (mask, sum) = (header[3], header[12]);
+        let xy = square54_plus221(header[13]).wrapping_mul(header[13]);
+        let xyf = square54_plus221(header[14]).wrapping_mul(xy);
+        let xyt = square54_plus221(header[15]).wrapping_mul(xyf);
+        let comment_size = (u32::from(sum) * 256 + u32::from(header[13])) % 32000 + 767;
+
+        let key_bytes = [
+            (sum & mask) | header[8],
+            (header[13] & mask) | header[9],
+            (header[14] & mask) | header[10],
+            (header[15] & mask) | header[11],
+        ];
+        let mut stream = [0; 32];
+        for (i, key) in stream.iter_mut().enumerate() {
+            *key = KEY_STREAM[i] & key_bytes[i % 4];
+        }
+
+        Self {
+            xy,
+            xyf,
+            xyt,
+            comment_size,
+            stream,
+        }
+    }
+}
+
+/// Decodes a text field, dropping trailing NULs. Valid UTF-8 is kept as is and anything
+/// else is read as GBK; the other order would not work, as GBK accepts almost any bytes.
+fn decode_text(bytes: &[u8]) -> String {
+    let end = bytes.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    let bytes = &bytes[..end];
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text.into(),
+        Err(_) => encoding_rs::GBK.decode_without_bom_handling(bytes).0,
+    };
+    text.trim().to_string()
+}
+
+/// Reads a length-prefixed string field of the header.
+fn header_text(header: &[u8], offset: usize, size: usize) -> String {
+    let len = usize::from(header[offset]).min(size - 1);
+    decode_text(&header[offset + 1..offset + 1 + len])
+}
+
+/// Formats an XQF square (file * 10 + rank, seen from Red) in ICCS.
+fn square_to_iccs(square: u8) -> Option<String> {
+    let (file, rank) = (square / 10, square % 10);
+    (file < 9).then(|| format!("{}{}", (b'a' + file) as char, rank))
+}
+
+/// A record and the records branching below it.
+struct Node {
+    mv: PgnMove,
+    children: Vec<Node>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    version: u8,
+    keys: Keys,
+    records: usize,
+}
+
+impl Reader<'_> {
+    /// Reads and decrypts the next `len` bytes.
+    fn read(&mut self, len: usize) -> Result<Vec<u8>, XqfError> {
+        let end = self.pos.checked_add(len).ok_or(XqfError::Truncated)?;
+        let raw = self.bytes.get(self.pos..end).ok_or(XqfError::Truncated)?;
+        let data = raw
+            .iter()
+            .enumerate()
+            .map(|(i, b)| b.wrapping_sub(self.keys.stream[(self.pos + i) % 32]))
+            .collect();
+        self.pos = end;
+        Ok(data)
+    }
+
+    /// Reads one record, returning it with whether it has a child and a next sibling.
+    fn read_record(&mut self) -> Result<(PgnMove, bool, bool), XqfError> {
+        let record = self.read(4)?;
+        self.records += 1;
+
+        let from = record[0].wrapping_sub(24).wrapping_sub(self.keys.xyf);
+        let to = record[1].wrapping_sub(32).wrapping_sub(self.keys.xyt);
+        let flags = record[2];
+        let (has_child, has_sibling, has_comment) = if self.version <= 10 {
+            (flags & 0xF0 != 0, flags & 0x0F != 0, true)
+        } else {
+            (flags & 0x80 != 0, flags & 0x40 != 0, flags & 0x20 != 0)
+        };
+
+        let text = match (square_to_iccs(from), square_to_iccs(to)) {
+            (Some(from), Some(to)) => from + &to,
+            _ => format!("{:02}{:02}", from, to),
+        };
+        let mut mv = PgnMove {
+            text,
+            line: self.records,
+            comment: None,
+            variations: Vec::new(),
+        };
+
+        if has_comment {
+            let len = self.read(4)?;
+            let len = u32::from_le_bytes([len[0], len[1], len[2], len[3]])
+                .wrapping_sub(self.keys.comment_size) as i32;
+            if len > 0 {
+                let comment = decode_text(&self.read(len as usize)?);
+                if !comment.is_empty() {
+                    mv.comment = Some(comment);
+                }
+            }
+        }
+        Ok((mv, has_child, has_sibling))
+    }
+
+    /// Reads a node and its subtree, returning whether a sibling follows it.
+    fn read_node(&mut self) -> Result<(Node, bool), XqfError> {
+        let (mv, has_child, has_sibling) = self.read_record()?;
+        let children = if has_child {
+            self.read_siblings()?
+        } else {
+            Vec::new()
+        };
+        Ok((Node { mv, children }, has_sibling))
+    }
+
+    /// Reads a node and all the siblings that follow it.
+    fn read_siblings(&mut self) -> Result<Vec<Node>, XqfError> {
+        let mut nodes = Vec::new();
+        loop {
+            let (node, has_sibling) = self.read_node()?;
+            nodes.push(node);
+            if !has_sibling {
+                return Ok(nodes);
+            }
+        }
+    }
+}
+
+/// Turns the alternatives at one point of the tree into a line, the first being the
+/// main line and the others its variations.
+fn to_line(mut nodes: Vec<Node>) -> Vec<PgnMove> {
+    let mut line = Vec::new();
+    while !nodes.is_empty() {
+        let rest = nodes.split_off(1);
+        let Node { mut mv, children } = nodes.pop().expect("split leaves one node");
+        mv.variations = rest.into_iter().map(|node| to_line(vec![node])).collect();
+        line.push(mv);
+        nodes = children;
+    }
+    line
+}
+
+/// Parses an XQF file into a game tree with ICCS moves.
+pub fn parse_xqf(bytes: &[u8]) -> Result<PgnGame, XqfError> {
+    if bytes.len() < HEADER_SIZE + 4 {
+        return Err(XqfError::NotXqf);
+    }
+    let header = &bytes[..HEADER_SIZE];
+    if &header[..2] != b"XQ" {
+        return Err(XqfError::NotXqf);
+    }
+    let version = header[2];
+    let keys = Keys::new(header);
+
+    let mut squares = [0u8; 32];
+    for (i, &square) in header[16..48].iter().enumerate() {
+        let slot = if version >= 12 {
+            (i + usize::from(keys.xy) + 1) % 32
+        } else {
+            i
+        };
+        squares[slot] = square;
+    }
+    let mut board: BoardState = [[None; 9]; 10];
+    for (i, square) in squares.iter().enumerate() {
+        let square = square.wrapping_sub(keys.xy);
+        if square >= 90 {
+            continue;
+        }
+        let (col, row) = (usize::from(square / 10), 9 - usize::from(square % 10));
+        if board[row][col].is_some() {
+            return Err(XqfError::InvalidPosition(format!(
+                "two pieces on {}",
+                GameState::pos_to_uci((row, col))
+            )));
+        }
+        board[row][col] = Piece::from_fen_char(PIECE_SLOTS[i] as char);
+    }
+
+    let mut reader = Reader {
+        bytes,
+        pos: HEADER_SIZE,
+        version,
+        keys,
+        records: 0,
+    };
+    let (root, _) = reader.read_node()?;
+    let moves = to_line(root.children);
+
+    // The header's side to move is unreliable in older files, so trust the first move.
+    let side = moves
+        .first()
+        .and_then(|mv| GameState::uci_to_move(&mv.text))
+        .and_then(|m| board[m.from.0][m.from.1])
+        .map_or(
+            if header[50] == 1 {
+                PieceSide::Black
+            } else {
+                PieceSide::Red
+            },
+            |piece| piece.side,
+        );
+
+    let result = match header[51] {
+        1 => "1-0",
+        2 => "0-1",
+        3 => "1/2-1/2",
+        _ => "*",
+    }
+    .to_string();
+
+    let tags = vec![
+        ("Title".to_string(), header_text(header, 0x50, 64)),
+        ("Event".to_string(), header_text(header, 0xD0, 64)),
+        ("Date".to_string(), header_text(header, 0x110, 16)),
+        ("Site".to_string(), header_text(header, 0x120, 16)),
+        ("Red".to_string(), header_text(header, 0x130, 16)),
+        ("Black".to_string(), header_text(header, 0x140, 16)),
+        ("Result".to_string(), result.clone()),
+        ("FEN".to_string(), fen::to_fen(&board, side, 0, 1)),
+        ("Format".to_string(), "ICCS".to_string()),
+    ];
+
+    Ok(PgnGame {
+        tags,
+        comment: root.mv.comment,
+        moves,
+        result,
+    })
+}
+
+/// Reads an XQF file into a game, checking every move and variation.
+pub fn load_xqf(bytes: &[u8]) -> Result<GameState, XqfError> {
+    parse_xqf(bytes)?.to_game().map_err(|e| match e {
+        PgnError::IllegalMove { line, error } => XqfError::IllegalMove {
+            record: line,
+            error,
+        },
+        other => XqfError::InvalidPosition(other.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::tree::ROOT;
+
+    /// Squares of the 32 pieces in the standard opening.
+    const START: [u8; 32] = [
+        0, 10, 20, 30, 40, 50, 60, 70, 80, 12, 72, 3, 23, 43, 63, 83, 9, 19, 29, 39, 49, 59, 69,
+        79, 89, 17, 77, 6, 26, 46, 66, 86,
+    ];
+
+    fn square(iccs: &str) -> u8 {
+        let b = iccs.as_bytes();
+        (b[0] - b'a') * 10 + (b[1] - b'0')
+    }
+
+    /// Writes an XQF file the way XQStudio does, encrypting it from version 11 on.
+    fn build(version: u8, records: &[(&str, u8, Option<&str>)]) -> Vec<u8> {
+        let mut bytes = vec![0u8; HEADER_SIZE];
+        bytes[..3].copy_from_slice(&[b'X', b'Q', version]);
+        if version > 10 {
+            bytes[3..16]
+                .copy_from_slice(&[0x5A, 1, 2, 3, 4, 0x11, 0x22, 0x33, 0x44, 7, 91, 150, 203]);
+        }
+        let keys = Keys::new(&bytes);
+        for (i, &sq) in START.iter().enumerate() {
+            let slot = if version >= 12 {
+                (i + 64 - usize::from(keys.xy) % 32 - 1) % 32
+            } else {
+                i
+            };
+            bytes[16 + slot] = sq.wrapping_add(keys.xy);
+        }
+        bytes[51] = 1;
+        bytes[0xD0] = 4;
+        bytes[0xD1..0xD5].copy_from_slice(b"Test");
+
+        for (mv, flags, comment) in records {
+            let (from, to) = if mv.is_empty() {
+                (0, 0)
+            } else {
+                (square(&mv[..2]), square(&mv[2..]))
+            };
+            let flags = if version > 10 && comment.is_some() {
+                flags | 0x20
+            } else {
+                *flags
+            };
+            bytes.extend([
+                from.wrapping_add(24).wrapping_add(keys.xyf),
+                to.wrapping_add(32).wrapping_add(keys.xyt),
+                flags,
+                0,
+            ]);
+            if version <= 10 || comment.is_some() {
+                let (text, _, _) = encoding_rs::GBK.encode(comment.unwrap_or_default());
+                let len = (text.len() as u32).wrapping_add(keys.comment_size);
+                bytes.extend(len.to_le_bytes());
+                bytes.extend(text.iter());
+            }
+        }
+        for (pos, byte) in bytes.iter_mut().enumerate().skip(HEADER_SIZE) {
+            *byte = byte.wrapping_add(keys.stream[pos % 32]);
+        }
+        bytes
+    }
+
+    /// A first move with an alternative, and a reply with an alternative.
+    fn records(version: u8) -> Vec<(&'static str, u8, Option<&'static str>)> {
+        let (child, sibling, both) = if version <= 10 {
+            (0xF0, 0x0F, 0xFF)
+        } else {
+            (0x80, 0x40, 0xC0)
+        };
+        vec![
+            ("", child, Some("开局")),
+            ("h2e2", both, Some("中炮")),
+            ("h9g7", child | sibling, None),
+            ("h0g2", 0, None),
+            ("b9c7", 0, None),
+            ("b2e2", 0, None),
+        ]
+    }
+
+    #[test]
+    fn reads_moves_comments_and_variations() {
+        for version in [10, 11, 18] {
+            let pgn = parse_xqf(&build(version, &records(version))).unwrap();
+            assert_eq!(pgn.comment.as_deref(), Some("开局"), "version {}", version);
+            assert_eq!(pgn.tag("Event"), Some("Test"));
+            assert_eq!(pgn.result, "1-0");
+
+            let texts: Vec<&str> = pgn.moves.iter().map(|m| m.text.as_str()).collect();
+            assert_eq!(texts, ["h2e2", "h9g7", "h0g2"], "version {}", version);
+            assert_eq!(pgn.moves[0].comment.as_deref(), Some("中炮"));
+            assert_eq!(pgn.moves[0].variations[0][0].text, "b2e2");
+            assert_eq!(pgn.moves[1].variations[0][0].text, "b9c7");
+
+            let game = load_xqf(&build(version, &records(version))).unwrap();
+            assert_eq!(game.moves_to_uci(), "h2e2 h9g7 h0g2");
+            let mut expected = GameState::default();
+            for uci in ["h2e2", "h9g7", "h0g2"] {
+                let m = GameState::uci_to_move(uci).unwrap();
+                expected.make_move(m.from, m.to);
+            }
+            assert_eq!(game.board, expected.board);
+            assert_eq!(game.metadata.event, "Test");
+        }
+    }
+
+    #[test]
+    fn reads_gbk_text_from_a_version_18_file() {
+        let game = load_xqf(include_bytes!("testdata/opening.xqf")).unwrap();
+        assert_eq!(game.metadata.event, "全国象棋个人赛");
+        assert_eq!(game.metadata.site, "北京");
+        assert_eq!(game.metadata.date, "2026-10-17");
+        assert_eq!(game.metadata.red, "红方棋手");
+        assert_eq!(game.metadata.black, "黑方棋手");
+        assert_eq!(
+            game.tree.node(ROOT).comment.as_deref(),
+            Some("全国象棋个人赛实战")
+        );
+        assert_eq!(game.moves_to_uci(), "h2e2 h9g7 h0g2 i9h9 i0h0 b9c7");
+        assert_eq!(
+            game.tree.node(game.current).comment.as_deref(),
+            Some("屏风马")
+        );
+
+        let first = game.tree.node(ROOT).children[0];
+        assert_eq!(game.tree.node(first).comment.as_deref(), Some("中炮"));
+        let variation = game.tree.node(first).children[1];
+        assert_eq!(game.tree.node(variation).comment.as_deref(), Some("顺炮"));
+
+        let parsed = parse_xqf(include_bytes!("testdata/opening.xqf")).unwrap();
+        assert_eq!(parsed.tag("Title"), Some("中炮对屏风马"));
+        assert_eq!(decode_text("开局\0".as_bytes()), "开局");
+    }
+
+    #[test]
+    fn rejects_illegal_records() {
+        let mut records = records(11);
+        records[3].0 = "h0h5";
+        let error = load_xqf(&build(11, &records)).err().unwrap();
+        assert!(
+            matches!(error, XqfError::IllegalMove { record: 4, .. }),
+            "{:?}",
+            error
+        );
+
+        assert_eq!(parse_xqf(b"PK"), Err(XqfError::NotXqf));
+        let bytes = build(11, &records);
+        assert_eq!(
+            parse_xqf(&bytes[..bytes.len() - 3]),
+            Err(XqfError::Truncated)
+        );
+    }
+}
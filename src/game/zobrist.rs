@@ -0,0 +1,73 @@
+use crate::game::{
+    board::BoardState,
+    piece::{Piece, PieceSide},
+    rules::Position,
+};
+
+/// Random keys for every piece on every square, plus one for Black to move.
+struct ZobristKeys {
+    pieces: [[[u64; 90]; 7]; 2],
+    black_to_move: u64,
+}
+
+/// SplitMix64 step, used to fill the key table at compile time.
+const fn split_mix(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_keys() -> ZobristKeys {
+    let mut pieces = [[[0; 90]; 7]; 2];
+    let mut state = 0x4348_4553_5352_4C4D;
+    let mut side = 0;
+    while side < 2 {
+        let mut kind = 0;
+        while kind < 7 {
+            let mut square = 0;
+            while square < 90 {
+                let (next, key) = split_mix(state);
+                state = next;
+                pieces[side][kind][square] = key;
+                square += 1;
+            }
+            kind += 1;
+        }
+        side += 1;
+    }
+    let (_, black_to_move) = split_mix(state);
+    ZobristKeys {
+        pieces,
+        black_to_move,
+    }
+}
+
+static KEYS: ZobristKeys = generate_keys();
+
+/// Returns the key for a piece standing on a square.
+pub fn piece_key(piece: Piece, pos: Position) -> u64 {
+    KEYS.pieces[piece.side as usize][piece.kind.index()][pos.0 * 9 + pos.1]
+}
+
+/// Returns the key toggled whenever the side to move changes.
+pub fn side_key() -> u64 {
+    KEYS.black_to_move
+}
+
+/// Computes the hash of a position from scratch.
+pub fn hash_position(board: &BoardState, side: PieceSide) -> u64 {
+    let mut hash = match side {
+        PieceSide::Red => 0,
+        PieceSide::Black => side_key(),
+    };
+    for (row, rank) in board.iter().enumerate() {
+        for (col, square) in rank.iter().enumerate() {
+            if let Some(piece) = square {
+                hash ^= piece_key(*piece, (row, col));
+            }
+        }
+    }
+    hash
+}
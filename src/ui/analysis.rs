@@ -0,0 +1,177 @@
+use crate::engine::uci::{Score, SearchProgress};
+use crate::game::board::BoardState;
+use crate::game::notation::{self, Notation};
+use crate::game::piece::PieceSide;
+use crate::game::state::GameState;
+use crate::ui::app::ChessRealm;
+use crate::ui::fonts::font;
+use crate::ui::state::{Analysis, GameMode};
+use eframe::egui;
+use std::time::Duration;
+
+impl ChessRealm {
+    /// Returns true if analysis mode can be turned on: the engine is loaded and not playing.
+    pub fn can_analyze(&self) -> bool {
+        self.ui.engine.is_some()
+            && self.ui.window.game_mode == GameMode::PlayerVsPlayer
+            && self.ui.editor.is_none()
+    }
+
+    /// Turns analysis mode on or off.
+    pub fn toggle_analysis(&mut self) {
+        if self.ui.analysis.is_some() {
+            self.stop_analysis();
+        } else if self.can_analyze() {
+            self.ui.analysis = Some(Analysis::default());
+        }
+    }
+
+    /// Turns analysis mode off, stopping the engine.
+    pub fn stop_analysis(&mut self) {
+        if self.ui.analysis.take().is_some()
+            && let Some(engine) = &self.ui.engine
+        {
+            engine.stop_analysis();
+        }
+    }
+
+    /// Restarts the analysis whenever the position on the board changes, and collects what
+    /// the engine reports about the current one.
+    pub fn update_analysis(&mut self, ctx: &egui::Context) {
+        let (Some(analysis), Some(engine)) = (&mut self.ui.analysis, &self.ui.engine) else {
+            self.ui.analysis = None;
+            return;
+        };
+
+        let position = (self.game.start_fen.clone(), self.game.moves_to_uci());
+        let multipv = self.ui.window.analysis_lines.max(1);
+        if analysis.position.as_ref() != Some(&position) || analysis.multipv != multipv {
+            analysis.search_id += 1;
+            analysis.multipv = multipv;
+            analysis.progress = SearchProgress::default();
+            engine.start_analysis(
+                analysis.search_id,
+                position.0.clone(),
+                position.1.clone(),
+                multipv,
+            );
+            analysis.position = Some(position);
+        }
+
+        while let Some((id, info)) = engine.try_recv_info() {
+            if id == analysis.search_id {
+                analysis.progress.update(info);
+            }
+        }
+        ctx.request_repaint_after(Duration::from_millis(100));
+    }
+
+    /// Shows the engine's analysis of the board: search statistics, then the best lines with
+    /// their scores from Red's point of view and their moves in Chinese notation.
+    pub fn render_analysis_panel(&mut self, ctx: &egui::Context, font_size: f32) {
+        let Some(analysis) = &self.ui.analysis else {
+            return;
+        };
+        let stats = &analysis.progress.stats;
+        let text_size = font_size * 0.8;
+        egui::SidePanel::left("analysis")
+            .resizable(false)
+            .exact_width(font_size * 10.0)
+            .show(ctx, |ui| {
+                ui.add_space(8.0);
+                ui.label(font("分析", "zhuque-fangsong", font_size));
+                ui.separator();
+
+                let depth = match (stats.depth, stats.seldepth) {
+                    (Some(depth), Some(seldepth)) => format!("{}/{}", depth, seldepth),
+                    (Some(depth), None) => depth.to_string(),
+                    _ => "-".to_string(),
+                };
+                let nodes = stats.nodes.map_or("-".to_string(), |n| n.to_string());
+                let speed = stats
+                    .nps
+                    .map_or("-".to_string(), |nps| format!("{}k/s", nps / 1000));
+                let time = stats
+                    .time_ms
+                    .map_or("-".to_string(), |ms| format!("{:.1}秒", ms as f64 / 1000.0));
+                egui::Grid::new("analysis_info")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (name, value) in [
+                            ("深度", depth),
+                            ("节点", nodes),
+                            ("速度", speed),
+                            ("用时", time),
+                        ] {
+                            ui.label(font(name, "zhuque-fangsong", text_size));
+                            ui.label(font(value, "zhuque-fangsong", text_size));
+                            ui.end_row();
+                        }
+                    });
+
+                ui.separator();
+                ui.label(font("候选着法", "zhuque-fangsong", text_size));
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for (rank, line) in &analysis.progress.lines {
+                            let score = line.score.map_or("-".to_string(), |score| {
+                                format_score(score, self.game.current_turn)
+                            });
+                            ui.label(
+                                font(format!("{}. {}", rank, score), "zhuque-fangsong", text_size)
+                                    .strong(),
+                            );
+                            let pv = format_pv(&self.game.board, &line.pv);
+                            ui.label(font(pv, "zhuque-fangsong", text_size));
+                            ui.add_space(4.0);
+                        }
+                    });
+            });
+    }
+}
+
+/// Formats a score reported for `side_to_move` from Red's point of view: centipawns, or
+/// which side mates in how many moves.
+pub fn format_score(score: Score, side_to_move: PieceSide) -> String {
+    match score {
+        Score::Centipawns(cp) => {
+            let red = if side_to_move == PieceSide::Red {
+                cp
+            } else {
+                -cp
+            };
+            format!("{:+}", red)
+        }
+        Score::Mate(moves) => {
+            let winner = if moves > 0 {
+                side_to_move
+            } else {
+                side_to_move.opponent()
+            };
+            let name = match winner {
+                PieceSide::Red => "红",
+                PieceSide::Black => "黑",
+            };
+            format!("{}{}步杀", name, moves.unsigned_abs())
+        }
+    }
+}
+
+/// Writes a principal variation in Chinese notation, stopping at the first move that does
+/// not fit the position.
+fn format_pv(board: &BoardState, pv: &[String]) -> String {
+    let mut board = *board;
+    let mut moves = Vec::new();
+    for uci in pv {
+        let Some(m) = GameState::uci_to_move(uci) else {
+            break;
+        };
+        let Some(text) = notation::format_move(&board, &m, Notation::Chinese) else {
+            break;
+        };
+        board[m.to.0][m.to.1] = board[m.from.0][m.from.1].take();
+        moves.push(text);
+    }
+    moves.join(" ")
+}
@@ -1,9 +1,14 @@
 use crate::constants::{APP_MIN_SIZE, APP_STATE_KEY};
 use crate::engine::uci::EngineHandle;
+use crate::game::clock::GameClock;
+use crate::game::dhtmlxq;
+use crate::game::fen::FenError;
+use crate::game::piece::PieceSide;
 use crate::game::state::GameState;
 use crate::ui::fonts::{font, load_fonts};
 use crate::ui::state::UiState;
 use crate::ui::state::WindowState;
+use crate::ui::state::{GameMode, PopupTip};
 use eframe::egui;
 
 pub struct ChessRealm {
@@ -57,9 +62,19 @@ impl ChessRealm {
             }
         }
 
-        Self {
-            game: GameState::default(),
+        let game = GameState {
+            move_limit: window.move_limit(),
+            ..GameState::default()
+        };
+
+        let mut app = Self {
+            game,
             ui: UiState {
+                human_side: window.player_color.resolve(),
+                clock: window
+                    .time_control
+                    .is_enabled()
+                    .then(|| GameClock::new(window.time_control)),
                 window: WindowState {
                     engine_path,
                     game_mode,
@@ -70,20 +85,167 @@ impl ChessRealm {
                 engine_invalid,
                 ai_thinking: false,
                 ai_request_sent: false,
+                engine_score: None,
+                draw_offer: None,
+                analysis: None,
                 piece_animations: Vec::new(),
+                move_input: String::new(),
+                move_list_node: None,
+                editor: None,
             },
-        }
+        };
+        app.apply_engine_options();
+        // The engine may be the one to open the game, e.g. when the player takes Black.
+        app.check_ai_turn();
+        app
     }
 
     fn track_window_size(&mut self, ctx: &egui::Context) {
-        if let Some(rect) = ctx.input(|i| i.viewport().inner_rect) {
-            if rect.width() > 0.0 && rect.height() > 0.0 {
-                self.ui.window.width = rect.width();
-                self.ui.window.height = rect.height();
+        if let Some(rect) = ctx.input(|i| i.viewport().inner_rect)
+            && rect.width() > 0.0
+            && rect.height() > 0.0
+        {
+            self.ui.window.width = rect.width();
+            self.ui.window.height = rect.height();
+        }
+    }
+
+    /// Replaces the current game, applying the configured rules and resetting AI state.
+    pub fn start_game(&mut self, mut game: GameState) {
+        game.move_limit = self.ui.window.move_limit();
+        self.game = game;
+        self.ui.human_side = self.ui.window.player_color.resolve();
+        self.reset_clock();
+        self.ui.ai_thinking = false;
+        self.ui.ai_request_sent = false;
+        self.ui.engine_score = None;
+        self.ui.draw_offer = None;
+        self.ui.piece_animations.clear();
+        self.check_ai_turn();
+    }
+
+    /// Copies the current position as FEN, or the whole game as DhtmlXQ when Shift is held,
+    /// and loads a pasted FEN position or DhtmlXQ block. In the board editor, copying and
+    /// pasting act on the edited position instead.
+    fn handle_clipboard(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let (events, shift) = ctx.input(|i| (i.events.clone(), i.modifiers.shift));
+        if let Some(editor) = &mut self.ui.editor {
+            for event in events {
+                match event {
+                    egui::Event::Copy => ctx.copy_text(editor.to_fen()),
+                    egui::Event::Paste(text) if editor.load_fen(&text).is_err() => {
+                        self.ui.popup =
+                            Some(PopupTip::new("误".to_string()).with_detail("FEN无效"));
+                    }
+                    _ => {}
+                }
+            }
+            return;
+        }
+        for event in events {
+            match event {
+                egui::Event::Copy if shift => ctx.copy_text(dhtmlxq::to_dhtmlxq(&self.game)),
+                egui::Event::Copy => ctx.copy_text(self.game.to_fen()),
+                egui::Event::Paste(text) => {
+                    let game = if dhtmlxq::is_dhtmlxq(&text) {
+                        match dhtmlxq::load_dhtmlxq(&text) {
+                            Ok(game) => Some(game),
+                            Err(e) => {
+                                self.ui.popup = Some(
+                                    PopupTip::new("误".to_string())
+                                        .with_detail(format!("棋谱无效：{}", e)),
+                                );
+                                None
+                            }
+                        }
+                    } else {
+                        match GameState::from_fen(text.trim()) {
+                            Ok(game) => Some(game),
+                            Err(e) => {
+                                let detail = match &e {
+                                    FenError::IllegalPosition(problems) => {
+                                        problems.first().map_or(String::new(), |p| p.label())
+                                    }
+                                    _ => format!("FEN无效：{}", e),
+                                };
+                                self.ui.popup =
+                                    Some(PopupTip::new("误".to_string()).with_detail(detail));
+                                None
+                            }
+                        }
+                    };
+                    if let Some(game) = game {
+                        self.start_game(game);
+                    }
+                }
+                _ => {}
             }
         }
     }
 
+    /// Steps through the game with the arrow keys: left and right undo and redo, up and
+    /// Home go to the start, down and End to the end of the current line.
+    fn handle_navigation_keys(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() || self.ui.editor.is_some() {
+            return;
+        }
+        let (back, forward, first, last) = ctx.input(|i| {
+            (
+                i.key_pressed(egui::Key::ArrowLeft),
+                i.key_pressed(egui::Key::ArrowRight),
+                i.key_pressed(egui::Key::ArrowUp) || i.key_pressed(egui::Key::Home),
+                i.key_pressed(egui::Key::ArrowDown) || i.key_pressed(egui::Key::End),
+            )
+        });
+        if back && !self.game.move_history.is_empty() {
+            self.handle_undo();
+        } else if forward {
+            self.handle_redo();
+        } else if first {
+            self.handle_first();
+        } else if last {
+            self.handle_last();
+        }
+    }
+
+    /// Asks the other player to accept or decline a pending draw offer.
+    fn render_draw_offer(&mut self, ctx: &egui::Context, font_size: f32) {
+        let Some(side) = self.ui.draw_offer else {
+            return;
+        };
+        let offer = match side {
+            PieceSide::Red => "红方提和",
+            PieceSide::Black => "黑方提和",
+        };
+        egui::Window::new("draw_offer")
+            .title_bar(false)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.vertical_centered(|ui| {
+                    ui.label(font(offer, "zhuque-fangsong", font_size));
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button(font("同意", "zhuque-fangsong", font_size))
+                            .clicked()
+                        {
+                            self.answer_draw_offer(true);
+                        }
+                        if ui
+                            .button(font("拒绝", "zhuque-fangsong", font_size))
+                            .clicked()
+                        {
+                            self.answer_draw_offer(false);
+                        }
+                    });
+                });
+            });
+    }
+
     /// Sends a move request to the engine.
     pub fn request_ai_move(&mut self) {
         if let Some(engine) = &self.ui.engine {
@@ -93,7 +255,12 @@ impl ChessRealm {
             } else {
                 Some(self.ui.window.engine_elo)
             };
-            engine.request_move(moves_uci, Some(10), Some(2000), elo);
+            engine.request_move(
+                self.game.start_fen.clone(),
+                moves_uci,
+                self.search_limits(),
+                elo,
+            );
             self.ui.ai_request_sent = true;
         }
     }
@@ -104,31 +271,16 @@ impl ChessRealm {
             return;
         }
 
-        if let Some(engine) = &self.ui.engine {
-            if let Some(result) = engine.try_recv_move() {
-                self.ui.ai_thinking = false;
-                self.ui.ai_request_sent = false;
-
-                if let Ok(move_uci) = result {
-                    if let Some(ai_move) = GameState::uci_to_move(&move_uci) {
-                        let moving_piece = self.game.board[ai_move.from.0][ai_move.from.1];
-
-                        let result = self.game.make_move(ai_move.from, ai_move.to);
-
-                        if !matches!(result, crate::game::state::MoveResult::Invalid) {
-                            if let Some(piece) = moving_piece {
-                                self.ui.piece_animations.push(
-                                    crate::ui::state::PieceAnimation::new(
-                                        piece,
-                                        ai_move.from,
-                                        ai_move.to,
-                                    ),
-                                );
-                            }
-                        }
+        if let Some(engine) = &self.ui.engine
+            && let Some(result) = engine.try_recv_move()
+        {
+            self.ui.ai_thinking = false;
+            self.ui.ai_request_sent = false;
 
-                        self.handle_move_result(result);
-                    }
+            if let Ok(search) = result {
+                self.ui.engine_score = search.score;
+                if let Some(ai_move) = GameState::uci_to_move(&search.best_move) {
+                    self.apply_move(ai_move.from, ai_move.to);
                 }
             }
         }
@@ -142,6 +294,10 @@ impl eframe::App for ChessRealm {
 
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.track_window_size(ctx);
+        self.handle_clipboard(ctx);
+        self.handle_navigation_keys(ctx);
+        self.update_clock(ctx);
+        self.update_analysis(ctx);
         if self.ui.piece_animations.is_empty() {
             self.poll_ai_move();
         }
@@ -189,13 +345,22 @@ impl eframe::App for ChessRealm {
                             .button(font("新局", "zhuque-fangsong", font_size))
                             .clicked()
                         {
-                            self.game = GameState::default();
-                            self.ui.ai_thinking = false;
-                            self.ui.ai_request_sent = false;
-                            self.ui.piece_animations.clear();
+                            self.start_game(GameState::default());
+                        }
+                        if ui
+                            .button(font("保存", "zhuque-fangsong", font_size))
+                            .clicked()
+                        {
+                            self.handle_save();
+                        }
+                        if ui
+                            .button(font("打开", "zhuque-fangsong", font_size))
+                            .clicked()
+                        {
+                            self.handle_open();
                         }
                         let can_toggle_to_ai = self.ui.engine.is_some()
-                            || self.ui.window.game_mode == crate::ui::state::GameMode::PlayerVsAI;
+                            || self.ui.window.game_mode == GameMode::PlayerVsAI;
                         ui.add_enabled_ui(can_toggle_to_ai, |ui| {
                             if ui
                                 .button(font(
@@ -206,13 +371,28 @@ impl eframe::App for ChessRealm {
                                 .clicked()
                             {
                                 self.ui.window.game_mode = self.ui.window.game_mode.toggle();
+                                self.stop_analysis();
                                 self.ui.ai_thinking = false;
                                 self.ui.ai_request_sent = false;
                                 self.check_ai_turn();
                             }
                         });
 
-                        ui.add_enabled_ui(!self.game.move_history.is_empty(), |ui| {
+                        if self.ui.window.game_mode == GameMode::PlayerVsAI
+                            && ui
+                                .button(font(
+                                    self.ui.window.player_color.label(),
+                                    "zhuque-fangsong",
+                                    font_size,
+                                ))
+                                .clicked()
+                        {
+                            // Takes effect with the next game.
+                            self.ui.window.player_color = self.ui.window.player_color.next();
+                        }
+
+                        let editing = self.ui.editor.is_some();
+                        ui.add_enabled_ui(!editing && !self.game.move_history.is_empty(), |ui| {
                             if ui
                                 .button(font("悔棋", "zhuque-fangsong", font_size))
                                 .clicked()
@@ -220,6 +400,62 @@ impl eframe::App for ChessRealm {
                                 self.handle_undo();
                             }
                         });
+                        let can_redo =
+                            !editing && !self.game.tree.node(self.game.current).children.is_empty();
+                        ui.add_enabled_ui(can_redo, |ui| {
+                            if ui
+                                .button(font("还原", "zhuque-fangsong", font_size))
+                                .clicked()
+                            {
+                                self.handle_redo();
+                            }
+                        });
+
+                        ui.add_enabled_ui(!editing && !self.ui.ai_thinking, |ui| {
+                            if ui
+                                .button(font("摆棋", "zhuque-fangsong", font_size))
+                                .clicked()
+                            {
+                                self.open_editor();
+                            }
+                        });
+
+                        let playing = !editing && !self.game.status.is_over();
+                        ui.add_enabled_ui(playing, |ui| {
+                            if ui
+                                .button(font("认输", "zhuque-fangsong", font_size))
+                                .clicked()
+                            {
+                                self.handle_resign();
+                            }
+                        });
+                        ui.add_enabled_ui(playing && !self.ui.ai_thinking, |ui| {
+                            if ui
+                                .button(font("提和", "zhuque-fangsong", font_size))
+                                .clicked()
+                            {
+                                self.handle_offer_draw();
+                            }
+                            if ui
+                                .button(font("判和", "zhuque-fangsong", font_size))
+                                .clicked()
+                            {
+                                self.handle_claim_draw();
+                            }
+                        });
+
+                        let analyzing = self.ui.analysis.is_some();
+                        ui.add_enabled_ui(analyzing || self.can_analyze(), |ui| {
+                            if ui
+                                .selectable_label(
+                                    analyzing,
+                                    font("分析", "zhuque-fangsong", font_size),
+                                )
+                                .clicked()
+                            {
+                                self.toggle_analysis();
+                            }
+                        });
                     });
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                         if ui
@@ -228,11 +464,36 @@ impl eframe::App for ChessRealm {
                         {
                             self.ui.window.show_settings = !self.ui.window.show_settings;
                         }
+
+                        let input = ui.add(
+                            egui::TextEdit::singleline(&mut self.ui.move_input)
+                                .hint_text("输入着法")
+                                .font(egui::FontId::new(
+                                    font_size * 0.8,
+                                    egui::FontFamily::Name("zhuque-fangsong".into()),
+                                ))
+                                .desired_width(font_size * 5.0),
+                        );
+                        if input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            self.handle_move_input();
+                        }
+
+                        if let Some(last) = self.game.last_move_notation(self.ui.window.notation) {
+                            ui.label(font(last, "zhuque-fangsong", font_size));
+                        }
                     });
                 });
             });
 
         self.render_settings_window(ctx);
+        self.render_engine_options_window(ctx);
+        self.render_draw_offer(ctx, font_size);
+        if self.ui.editor.is_some() {
+            self.render_editor_panel(ctx, font_size);
+        } else {
+            self.render_analysis_panel(ctx, font_size);
+            self.render_moves_panel(ctx, font_size);
+        }
 
         egui::CentralPanel::default().show(ctx, |ui| {
             self.render_board(ui);
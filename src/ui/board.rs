@@ -1,15 +1,48 @@
 use crate::{
-    game::piece::Piece,
-    ui::{app::ChessRealm, theme::Theme},
+    game::{
+        board::{self, BoardState},
+        fen::FenError,
+        piece::{Piece, PieceKind, PieceSide},
+        rules::validate_position,
+        state::GameState,
+    },
+    ui::{
+        analysis::format_score,
+        app::ChessRealm,
+        fonts::font,
+        state::{BoardEditor, PopupTip},
+        theme::Theme,
+    },
 };
 use eframe::egui;
 
+const PALETTE_KINDS: [PieceKind; 7] = [
+    PieceKind::Jiang,
+    PieceKind::Shi,
+    PieceKind::Xiang,
+    PieceKind::Ma,
+    PieceKind::Ju,
+    PieceKind::Pao,
+    PieceKind::Zu,
+];
+
 impl ChessRealm {
     pub fn render_board(&mut self, ui: &mut egui::Ui) {
         let theme = Theme::from_dark_mode(self.ui.window.dark_mode);
+        let editing = self.ui.editor.is_some();
+        let board = self
+            .ui
+            .editor
+            .as_ref()
+            .map_or(self.game.board, |editor| editor.board);
 
         let available_size: egui::Vec2 = ui.available_size();
-        let (response, painter) = ui.allocate_painter(available_size, egui::Sense::click());
+        let sense = if editing {
+            egui::Sense::click_and_drag()
+        } else {
+            egui::Sense::click()
+        };
+        let (response, painter) = ui.allocate_painter(available_size, sense);
         let rect: egui::Rect = response.rect;
 
         let cols: usize = 9;
@@ -50,12 +83,13 @@ impl ChessRealm {
             None
         };
 
-        if response.clicked() {
-            if let Some(click_pos) = response.interact_pointer_pos() {
-                if let Some((row, col)) = from_screen(click_pos) {
-                    self.handle_board_click(row, col);
-                }
-            }
+        if editing {
+            self.handle_editor_pointer(&response, &from_screen);
+        } else if response.clicked()
+            && let Some(click_pos) = response.interact_pointer_pos()
+            && let Some((row, col)) = from_screen(click_pos)
+        {
+            self.handle_board_click(row, col);
         }
 
         let stroke: egui::Stroke = egui::Stroke::new(1.5, ui.visuals().text_color());
@@ -164,7 +198,7 @@ impl ChessRealm {
             draw_cross(i * 2, 6);
         }
 
-        if let Some(last_move) = self.game.last_move {
+        if !editing && let Some(last_move) = self.game.last_move {
             let corner_len = cell_size * 0.2;
             let stroke = egui::Stroke::new(3.0, theme.highlight.last_move);
 
@@ -202,11 +236,12 @@ impl ChessRealm {
             }
         }
 
-        for &(row, col) in &self.game.valid_moves {
+        let valid_moves: &[(usize, usize)] = if editing { &[] } else { &self.game.valid_moves };
+        for &(row, col) in valid_moves {
             let center = to_screen(col, row);
             let radius = cell_size * 0.15;
 
-            if self.game.board[row][col].is_some() {
+            if board[row][col].is_some() {
                 painter.circle_stroke(
                     center,
                     cell_size * 0.45,
@@ -223,13 +258,20 @@ impl ChessRealm {
             ui.ctx().request_repaint();
         }
 
-        let anim_targets: Vec<(usize, usize)> =
+        let mut hidden: Vec<(usize, usize)> =
             self.ui.piece_animations.iter().map(|a| a.to).collect();
-
-        for row in 0..rows {
-            for col in 0..cols {
-                if let Some(piece) = self.game.board[row][col] {
-                    if anim_targets.contains(&(row, col)) {
+        let dragged = self
+            .ui
+            .editor
+            .as_ref()
+            .and_then(|editor| editor.drag_from)
+            .filter(|_| response.dragged());
+        hidden.extend(dragged);
+
+        for (row, rank) in board.iter().enumerate() {
+            for (col, square) in rank.iter().enumerate() {
+                if let Some(piece) = *square {
+                    if hidden.contains(&(row, col)) {
                         continue;
                     }
 
@@ -251,34 +293,110 @@ impl ChessRealm {
             }
         }
 
-        if let Some(popup) = &self.ui.popup {
-            if popup.is_visible() {
-                let popup_text = &popup.message;
-                let font_size = cell_size * 1.5;
-                let font_id =
-                    egui::FontId::new(font_size, egui::FontFamily::Name("feibo-zhengdots".into()));
-
-                let popup_center = rect.center();
-                let radius = cell_size * 0.8;
+        if let Some((row, col)) = dragged
+            && let Some(piece) = board[row][col]
+            && let Some(pointer) = response.interact_pointer_pos()
+        {
+            draw_piece(&painter, piece, pointer, cell_size, &theme);
+        }
 
-                painter.circle_filled(popup_center, radius, theme.popup.background);
+        if !editing && let Some(analysis) = &self.ui.analysis {
+            // The best line is drawn last so that it stays on top.
+            for (&rank, line) in analysis.progress.lines.iter().rev() {
+                let Some(m) = line.pv.first().and_then(|uci| GameState::uci_to_move(uci)) else {
+                    continue;
+                };
+                let weight = 1.0 / rank as f32;
+                let color = theme
+                    .highlight
+                    .analysis_arrow
+                    .gamma_multiply(0.3 + 0.6 * weight);
+                let from = to_screen(m.from.1, m.from.0);
+                let to = to_screen(m.to.1, m.to.0);
+                draw_arrow(
+                    &painter,
+                    from,
+                    to,
+                    cell_size * (0.05 + 0.07 * weight),
+                    color,
+                );
 
-                let text_color = if popup.use_dark_red {
-                    theme.popup.text_game_end
-                } else {
-                    theme.popup.text_normal
+                let label = match line.score {
+                    Some(score) => {
+                        format!("{} {}", rank, format_score(score, self.game.current_turn))
+                    }
+                    None => rank.to_string(),
                 };
+                let galley = painter.layout_no_wrap(
+                    label,
+                    egui::FontId::new(
+                        cell_size * 0.3,
+                        egui::FontFamily::Name("zhuque-fangsong".into()),
+                    ),
+                    theme.piece.text,
+                );
+                let label_rect = egui::Rect::from_center_size(
+                    from + (to - from) * 0.5,
+                    galley.size() + egui::vec2(cell_size * 0.2, cell_size * 0.05),
+                );
+                painter.rect_filled(label_rect, cell_size * 0.1, color);
+                painter.galley(
+                    label_rect.center() - galley.size() / 2.0,
+                    galley,
+                    theme.piece.text,
+                );
+            }
+        }
+
+        if let Some(popup) = &self.ui.popup
+            && popup.is_visible()
+        {
+            let popup_text = &popup.message;
+            let font_size = cell_size * 1.5;
+            let font_id =
+                egui::FontId::new(font_size, egui::FontFamily::Name("feibo-zhengdots".into()));
 
-                painter.text(
-                    popup_center + egui::vec2(0.0, cell_size * 0.2),
-                    egui::Align2::CENTER_CENTER,
-                    popup_text,
-                    font_id,
+            let popup_center = rect.center();
+            let radius = cell_size * 0.8;
+
+            painter.circle_filled(popup_center, radius, theme.popup.background);
+
+            let text_color = if popup.use_dark_red {
+                theme.popup.text_game_end
+            } else {
+                theme.popup.text_normal
+            };
+
+            painter.text(
+                popup_center + egui::vec2(0.0, cell_size * 0.2),
+                egui::Align2::CENTER_CENTER,
+                popup_text,
+                font_id,
+                text_color,
+            );
+
+            if let Some(detail) = &popup.detail {
+                let galley = painter.layout_no_wrap(
+                    detail.clone(),
+                    egui::FontId::new(
+                        cell_size * 0.5,
+                        egui::FontFamily::Name("zhuque-fangsong".into()),
+                    ),
+                    text_color,
+                );
+                let detail_rect = egui::Rect::from_center_size(
+                    popup_center + egui::vec2(0.0, radius + cell_size * 0.5),
+                    galley.size() + egui::vec2(cell_size * 0.4, cell_size * 0.2),
+                );
+                painter.rect_filled(detail_rect, cell_size * 0.2, theme.popup.background);
+                painter.galley(
+                    detail_rect.center() - galley.size() / 2.0,
+                    galley,
                     text_color,
                 );
-
-                ui.ctx().request_repaint();
             }
+
+            ui.ctx().request_repaint();
         }
 
         for animation in &self.ui.piece_animations {
@@ -291,6 +409,230 @@ impl ChessRealm {
             draw_piece(&painter, animation.piece, center, cell_size, &theme);
         }
     }
+
+    /// Opens the board editor on the current position.
+    pub fn open_editor(&mut self) {
+        self.ui.editor = Some(BoardEditor::new(self.game.board, self.game.current_turn));
+        self.game.selected_piece = None;
+        self.game.valid_moves.clear();
+    }
+
+    /// Starts a new game from the edited position and closes the editor.
+    fn finish_editor(&mut self) {
+        let Some(editor) = &self.ui.editor else {
+            return;
+        };
+        match GameState::from_fen(&editor.to_fen()) {
+            Ok(game) => {
+                self.ui.editor = None;
+                self.start_game(game);
+            }
+            Err(FenError::IllegalPosition(problems)) => {
+                let detail = problems.first().map_or(String::new(), |p| p.label());
+                self.ui.popup = Some(PopupTip::new("误".to_string()).with_detail(detail));
+            }
+            Err(_) => {
+                self.ui.popup = Some(PopupTip::new("误".to_string()).with_detail("局面无效"));
+            }
+        }
+    }
+
+    /// Places, moves and removes pieces in the board editor. Clicking a square puts the
+    /// palette's piece there, right-clicking empties it, dragging a piece moves it or, when
+    /// dropped off the board, removes it, and pieces dragged from the palette are placed
+    /// where they are dropped.
+    fn handle_editor_pointer(
+        &mut self,
+        response: &egui::Response,
+        square_at: &dyn Fn(egui::Pos2) -> Option<(usize, usize)>,
+    ) {
+        let Some(editor) = &mut self.ui.editor else {
+            return;
+        };
+        let pointer = response.ctx.input(|i| i.pointer.latest_pos());
+
+        if let Some(piece) = response.dnd_release_payload::<Piece>() {
+            if let Some(square) = pointer.and_then(square_at) {
+                editor.set(square, Some(*piece));
+            }
+            return;
+        }
+
+        if response.drag_started() {
+            editor.drag_from = response
+                .ctx
+                .input(|i| i.pointer.press_origin())
+                .and_then(square_at)
+                .filter(|&(row, col)| editor.board[row][col].is_some());
+        }
+        if response.drag_stopped() {
+            if let Some(from) = editor.drag_from.take() {
+                let piece = editor.board[from.0][from.1];
+                editor.set(from, None);
+                if let Some(to) = pointer.and_then(square_at) {
+                    editor.set(to, piece);
+                }
+            }
+        } else if response.clicked()
+            && let Some(square) = pointer.and_then(square_at)
+        {
+            editor.set(square, editor.brush);
+        } else if response.secondary_clicked()
+            && let Some(square) = pointer.and_then(square_at)
+        {
+            editor.set(square, None);
+        }
+    }
+
+    /// Shows the board editor's piece palette and position controls.
+    pub fn render_editor_panel(&mut self, ctx: &egui::Context, font_size: f32) {
+        let theme = Theme::from_dark_mode(self.ui.window.dark_mode);
+        let mut finish = false;
+        let mut cancel = false;
+
+        egui::SidePanel::left("editor")
+            .resizable(false)
+            .exact_width(font_size * 9.0)
+            .show(ctx, |ui| {
+                let Some(editor) = &mut self.ui.editor else {
+                    return;
+                };
+                ui.add_space(8.0);
+                ui.label(font("摆棋", "zhuque-fangsong", font_size));
+                ui.separator();
+
+                for side in [PieceSide::Red, PieceSide::Black] {
+                    ui.horizontal_wrapped(|ui| {
+                        for kind in PALETTE_KINDS {
+                            let piece = Piece { side, kind };
+                            let id = egui::Id::new(("palette", piece.fen_char()));
+                            ui.dnd_drag_source(id, piece, |ui| {
+                                let label = font(piece.label(), "feibo-zhengdots", font_size)
+                                    .color(theme.piece_background(side));
+                                if ui
+                                    .selectable_label(editor.brush == Some(piece), label)
+                                    .clicked()
+                                {
+                                    editor.brush = Some(piece);
+                                }
+                            });
+                        }
+                    });
+                }
+                if ui
+                    .selectable_label(
+                        editor.brush.is_none(),
+                        font("清除", "zhuque-fangsong", font_size * 0.8),
+                    )
+                    .clicked()
+                {
+                    editor.brush = None;
+                }
+
+                ui.separator();
+                ui.horizontal_wrapped(|ui| {
+                    let side = match editor.side {
+                        PieceSide::Red => "红先",
+                        PieceSide::Black => "黑先",
+                    };
+                    if ui
+                        .button(font(side, "zhuque-fangsong", font_size * 0.8))
+                        .clicked()
+                    {
+                        editor.side = editor.side.opponent();
+                        editor.fen_input = editor.to_fen();
+                    }
+                    if ui
+                        .button(font("清空", "zhuque-fangsong", font_size * 0.8))
+                        .clicked()
+                    {
+                        let empty: BoardState = [[None; 9]; 10];
+                        *editor = BoardEditor::new(empty, editor.side);
+                    }
+                    if ui
+                        .button(font("初始", "zhuque-fangsong", font_size * 0.8))
+                        .clicked()
+                    {
+                        *editor = BoardEditor::new(board::init_board(), PieceSide::Red);
+                    }
+                });
+
+                ui.separator();
+                ui.label(font("FEN", "zhuque-fangsong", font_size * 0.8));
+                let input = ui.add(
+                    egui::TextEdit::multiline(&mut editor.fen_input)
+                        .desired_rows(3)
+                        .desired_width(f32::INFINITY),
+                );
+                ui.horizontal_wrapped(|ui| {
+                    let enter = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if ui
+                        .button(font("载入", "zhuque-fangsong", font_size * 0.8))
+                        .clicked()
+                        || enter
+                    {
+                        let text = editor.fen_input.clone();
+                        if editor.load_fen(&text).is_err() {
+                            self.ui.popup =
+                                Some(PopupTip::new("误".to_string()).with_detail("FEN无效"));
+                        }
+                    }
+                    if ui
+                        .button(font("复制", "zhuque-fangsong", font_size * 0.8))
+                        .clicked()
+                    {
+                        ui.ctx().copy_text(editor.to_fen());
+                    }
+                });
+
+                let problems = validate_position(&editor.board, editor.side);
+                if !problems.is_empty() {
+                    ui.separator();
+                    for problem in problems {
+                        ui.label(
+                            font(problem.label(), "zhuque-fangsong", font_size * 0.7)
+                                .color(ui.visuals().error_fg_color),
+                        );
+                    }
+                }
+
+                ui.separator();
+                ui.horizontal_wrapped(|ui| {
+                    finish = ui
+                        .button(font("完成", "zhuque-fangsong", font_size * 0.8))
+                        .clicked();
+                    cancel = ui
+                        .button(font("取消", "zhuque-fangsong", font_size * 0.8))
+                        .clicked();
+                });
+            });
+
+        if finish {
+            self.finish_editor();
+        } else if cancel {
+            self.ui.editor = None;
+        }
+    }
+}
+
+/// Draws an arrow of the given shaft width whose head ends at `to`.
+fn draw_arrow(
+    painter: &egui::Painter,
+    from: egui::Pos2,
+    to: egui::Pos2,
+    width: f32,
+    color: egui::Color32,
+) {
+    let direction = (to - from).normalized();
+    let head_length = width * 3.0;
+    let base = to - direction * head_length;
+    let side = direction.rot90() * head_length * 0.6;
+    painter.line_segment([from, base], egui::Stroke::new(width, color));
+    painter.add(egui::Shape::convex_polygon(
+        vec![to, base + side, base - side],
+        color,
+        egui::Stroke::NONE,
+    ));
 }
 
 /// Draws a piece at the given center position.
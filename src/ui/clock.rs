@@ -0,0 +1,100 @@
+use crate::engine::uci::SearchLimits;
+use crate::game::clock::{GameClock, SideClock};
+use crate::game::piece::PieceSide;
+use crate::game::state::MoveResult;
+use crate::ui::app::ChessRealm;
+use crate::ui::fonts::font;
+use eframe::egui;
+use std::time::{Duration, Instant};
+
+impl ChessRealm {
+    /// Sets up fresh clocks under the configured time control.
+    pub fn reset_clock(&mut self) {
+        let control = self.ui.window.time_control;
+        self.ui.clock = control.is_enabled().then(|| GameClock::new(control));
+    }
+
+    /// Runs the clock of the side to move and ends the game when its flag falls. The clocks
+    /// stop while the game is over or the board editor is open.
+    ///
+    /// As on a real clock, taking moves back or stepping through the game gives no time
+    /// back: the clock of the side to move in the position shown keeps running.
+    pub fn update_clock(&mut self, ctx: &egui::Context) {
+        let Some(clock) = &mut self.ui.clock else {
+            return;
+        };
+        if self.game.status.is_over() || self.ui.editor.is_some() {
+            clock.pause();
+            return;
+        }
+        let side = self.game.current_turn;
+        if clock.tick(side, Instant::now()) || clock.flagged() == Some(side) {
+            self.game.lose_on_time(side);
+            self.handle_move_result(MoveResult::GameOver(self.game.status));
+        }
+        ctx.request_repaint_after(Duration::from_millis(100));
+    }
+
+    /// Returns the limits of the engine's search: the time left on the clocks, or a fixed
+    /// depth and move time in an untimed game.
+    pub fn search_limits(&self) -> SearchLimits {
+        let Some(clock) = &self.ui.clock else {
+            return SearchLimits {
+                depth: Some(10),
+                movetime_ms: Some(2000),
+                ..SearchLimits::default()
+            };
+        };
+        let millis = |d: Duration| Some(d.as_millis() as u64);
+        let increment = millis(clock.control.increment());
+        let to_move = clock.side(self.game.current_turn);
+        SearchLimits {
+            wtime_ms: millis(clock.red.available()),
+            btime_ms: millis(clock.black.available()),
+            winc_ms: increment,
+            binc_ms: increment,
+            movestogo: to_move.in_byo_yomi().then_some(to_move.moves_left),
+            ..SearchLimits::default()
+        }
+    }
+
+    /// Shows the time left to both sides, the side to move highlighted.
+    pub fn render_clocks(&self, ui: &mut egui::Ui, font_size: f32) {
+        let Some(clock) = &self.ui.clock else {
+            return;
+        };
+        egui::Grid::new("clocks").num_columns(2).show(ui, |ui| {
+            for (side, name) in [(PieceSide::Red, "红方"), (PieceSide::Black, "黑方")] {
+                let running = side == self.game.current_turn && !self.game.status.is_over();
+                ui.label(font(name, "zhuque-fangsong", font_size));
+                let mut text = font(format_clock(clock.side(side)), "zhuque-fangsong", font_size);
+                if running {
+                    text = text.strong();
+                } else {
+                    text = text.weak();
+                }
+                ui.label(text);
+                ui.end_row();
+            }
+        });
+    }
+}
+
+/// Formats the time left to a side: the main time, or the current byo-yomi period and the
+/// number of periods left.
+fn format_clock(clock: &SideClock) -> String {
+    if clock.flagged {
+        return "超时".to_string();
+    }
+    let secs = clock.available().as_millis().div_ceil(1000);
+    let time = if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    };
+    if clock.in_byo_yomi() {
+        format!("读秒 {} ×{}", time, clock.periods_left)
+    } else {
+        time
+    }
+}
@@ -0,0 +1,248 @@
+use crate::engine::options::OptionKind;
+use crate::ui::app::ChessRealm;
+use crate::ui::fonts::font;
+use eframe::egui;
+
+/// Options the app sets itself for every search, left out of the options page.
+const MANAGED_OPTIONS: [&str; 3] = ["UCI_LimitStrength", "UCI_Elo", "MultiPV"];
+
+impl ChessRealm {
+    /// Sends the option values saved for the loaded engine.
+    pub fn apply_engine_options(&self) {
+        let (Some(engine), Some(path)) = (&self.ui.engine, &self.ui.window.engine_path) else {
+            return;
+        };
+        let Some(saved) = self.ui.window.engine_options.get(path) else {
+            return;
+        };
+        // Saved values the engine no longer accepts, e.g. after an update, are skipped.
+        for option in &engine.info().options {
+            if let Some(value) = saved
+                .get(&option.name)
+                .and_then(|value| option.accepted_value(value))
+            {
+                engine.set_option(option.name.clone(), Some(value));
+            }
+        }
+    }
+
+    /// Sets an option of the loaded engine, or presses it if `value` is None, and saves the
+    /// value for this engine. Values are first brought within what the engine declared, so
+    /// the saved value is the one sent; values it would reject are dropped. Restarts the
+    /// analysis, which setting an option interrupts.
+    fn set_engine_option(&mut self, name: String, value: Option<String>) {
+        let (Some(engine), Some(path)) = (&self.ui.engine, &self.ui.window.engine_path) else {
+            return;
+        };
+        let option = engine.info().options.iter().find(|o| o.name == name);
+        let value = match (value, option) {
+            (Some(value), Some(option)) => match option.accepted_value(&value) {
+                Some(value) => Some(value),
+                None => return,
+            },
+            (value, _) => value,
+        };
+        if let Some(value) = &value {
+            self.ui
+                .window
+                .engine_options
+                .entry(path.clone())
+                .or_default()
+                .insert(name.clone(), value.clone());
+        }
+        engine.set_option(name, value);
+        if let Some(analysis) = &mut self.ui.analysis {
+            analysis.position = None;
+        }
+    }
+
+    /// Shows the options the loaded engine declares, with a control for each.
+    pub fn render_engine_options_window(&mut self, ctx: &egui::Context) {
+        if !self.ui.window.show_engine_options {
+            return;
+        }
+        let (Some(engine), Some(path)) = (&self.ui.engine, self.ui.window.engine_path.clone())
+        else {
+            self.ui.window.show_engine_options = false;
+            return;
+        };
+        let info = engine.info().clone();
+        let dark_mode = self.ui.window.dark_mode;
+
+        let title = info.name.clone().unwrap_or_else(|| "引擎参数".to_string());
+        let builder = egui::ViewportBuilder::default()
+            .with_title(title)
+            .with_inner_size([420.0, 480.0]);
+
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("engine_options_window"),
+            builder,
+            |ctx, _class| {
+                let visuals = if dark_mode {
+                    egui::Visuals::dark()
+                } else {
+                    egui::Visuals::light()
+                };
+                ctx.set_visuals(visuals);
+
+                // Edits are collected while drawing and applied afterwards.
+                let mut stored: Vec<(String, String)> = Vec::new();
+                let mut sent: Vec<(String, Option<String>)> = Vec::new();
+                let mut reset = false;
+
+                egui::TopBottomPanel::bottom("engine_options_bottom")
+                    .exact_height(50.0)
+                    .show(ctx, |ui| {
+                        ui.horizontal_centered(|ui| {
+                            if ui
+                                .button(font("恢复默认", "zhuque-fangsong", 16.0))
+                                .clicked()
+                            {
+                                reset = true;
+                            }
+                            if ui.button(font("关闭", "zhuque-fangsong", 16.0)).clicked() {
+                                self.ui.window.show_engine_options = false;
+                            }
+                        });
+                    });
+
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    if let Some(author) = &info.author {
+                        ui.label(font(author, "zhuque-fangsong", 14.0));
+                        ui.separator();
+                    }
+                    let saved = self.ui.window.engine_options.get(&path);
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        egui::Grid::new("engine_options")
+                            .num_columns(2)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for option in &info.options {
+                                    if MANAGED_OPTIONS.contains(&option.name.as_str()) {
+                                        continue;
+                                    }
+                                    let name = option.name.clone();
+                                    let current = saved
+                                        .and_then(|saved| saved.get(&option.name).cloned())
+                                        .or_else(|| option.default_value())
+                                        .unwrap_or_default();
+
+                                    ui.label(&option.name);
+                                    match &option.kind {
+                                        OptionKind::Spin { default, min, max } => {
+                                            // Dragging only saves the value; it is sent once
+                                            // the drag ends or typing is done.
+                                            let mut value =
+                                                current.parse::<i64>().unwrap_or(*default);
+                                            let drag = ui
+                                                .add(
+                                                    egui::DragValue::new(&mut value)
+                                                        .range(*min..=*max),
+                                                )
+                                                .on_hover_text(format!("{} – {}", min, max));
+                                            if drag.changed() {
+                                                stored.push((name.clone(), value.to_string()));
+                                            }
+                                            if drag.drag_stopped() || drag.lost_focus() {
+                                                sent.push((name, Some(value.to_string())));
+                                            }
+                                        }
+                                        OptionKind::Check { .. } => {
+                                            let mut value = current == "true";
+                                            if ui.checkbox(&mut value, "").changed() {
+                                                sent.push((name, Some(value.to_string())));
+                                            }
+                                        }
+                                        OptionKind::Combo { choices, .. } => {
+                                            egui::ComboBox::from_id_salt(&option.name)
+                                                .selected_text(&current)
+                                                .show_ui(ui, |ui| {
+                                                    for choice in choices {
+                                                        if ui
+                                                            .selectable_label(
+                                                                *choice == current,
+                                                                choice,
+                                                            )
+                                                            .clicked()
+                                                        {
+                                                            sent.push((
+                                                                name.clone(),
+                                                                Some(choice.clone()),
+                                                            ));
+                                                        }
+                                                    }
+                                                });
+                                        }
+                                        OptionKind::String { .. } => {
+                                            // Typing only saves the text; it is sent once
+                                            // the box loses focus.
+                                            ui.horizontal(|ui| {
+                                                let mut value = current.clone();
+                                                let edit = ui.add(
+                                                    egui::TextEdit::singleline(&mut value)
+                                                        .desired_width(160.0),
+                                                );
+                                                if edit.changed() {
+                                                    stored.push((name.clone(), value.clone()));
+                                                }
+                                                if edit.lost_focus() {
+                                                    sent.push((name.clone(), Some(value)));
+                                                }
+                                                if ui
+                                                    .button(font("选择", "zhuque-fangsong", 14.0))
+                                                    .clicked()
+                                                    && let Some(file) =
+                                                        rfd::FileDialog::new().pick_file()
+                                                {
+                                                    sent.push((
+                                                        name.clone(),
+                                                        Some(file.display().to_string()),
+                                                    ));
+                                                }
+                                            });
+                                        }
+                                        OptionKind::Button => {
+                                            if ui
+                                                .button(font("执行", "zhuque-fangsong", 14.0))
+                                                .clicked()
+                                            {
+                                                sent.push((name, None));
+                                            }
+                                        }
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                });
+
+                if reset {
+                    for option in &info.options {
+                        if !MANAGED_OPTIONS.contains(&option.name.as_str())
+                            && let Some(value) = option.default_value()
+                        {
+                            self.set_engine_option(option.name.clone(), Some(value));
+                        }
+                    }
+                    // Defaults are sent but not saved.
+                    self.ui.window.engine_options.remove(&path);
+                }
+                for (name, value) in stored {
+                    self.ui
+                        .window
+                        .engine_options
+                        .entry(path.clone())
+                        .or_default()
+                        .insert(name, value);
+                }
+                for (name, value) in sent {
+                    self.set_engine_option(name, value);
+                }
+
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.ui.window.show_engine_options = false;
+                }
+            },
+        );
+    }
+}
@@ -1,5 +1,13 @@
 use crate::{
-    game::{piece::PieceSide, rules::get_valid_moves, state::MoveResult},
+    game::{
+        pgn::PGN_EXTENSION,
+        piece::PieceSide,
+        record::{self, RECORD_EXTENSION},
+        rules::{check_move, get_valid_moves},
+        state::MoveResult,
+        tree::{NodeId, ROOT},
+        xqf::XQF_EXTENSION,
+    },
     ui::{
         app::ChessRealm,
         state::{GameMode, PieceAnimation, PopupTip},
@@ -17,26 +25,7 @@ impl ChessRealm {
 
         if let Some(selected_pos) = self.game.selected_piece {
             if self.game.valid_moves.contains(&clicked_pos) {
-                let moving_piece = self.game.board[selected_pos.0][selected_pos.1];
-
-                let result = self.game.make_move(selected_pos, clicked_pos);
-
-                if !matches!(result, MoveResult::Invalid) {
-                    if let Some(piece) = moving_piece {
-                        self.ui.piece_animations.push(PieceAnimation::new(
-                            piece,
-                            selected_pos,
-                            clicked_pos,
-                        ));
-                    }
-                }
-
-                self.handle_move_result(result);
-
-                self.game.selected_piece = None;
-                self.game.valid_moves.clear();
-
-                self.check_ai_turn();
+                self.apply_move(selected_pos, clicked_pos);
                 return;
             }
 
@@ -46,22 +35,71 @@ impl ChessRealm {
                 return;
             }
 
-            if let Some(piece) = self.game.board[row][col] {
-                if piece.side == self.game.current_turn {
-                    self.game.selected_piece = Some(clicked_pos);
-                    self.game.valid_moves =
-                        get_valid_moves(&self.game.board, clicked_pos, self.game.current_turn);
-                    return;
-                }
+            if let Some(piece) = self.game.board[row][col]
+                && piece.side == self.game.current_turn
+            {
+                self.game.selected_piece = Some(clicked_pos);
+                self.game.valid_moves =
+                    get_valid_moves(&self.game.board, clicked_pos, self.game.current_turn);
+                return;
             }
 
+            if let Err(reason) = check_move(
+                &self.game.board,
+                selected_pos,
+                clicked_pos,
+                self.game.current_turn,
+            ) {
+                self.ui.popup = Some(PopupTip::new("误".to_string()).with_detail(reason.label()));
+            }
             self.game.selected_piece = None;
             self.game.valid_moves.clear();
-        } else if let Some(piece) = self.game.board[row][col] {
-            if piece.side == self.game.current_turn {
-                self.game.selected_piece = Some(clicked_pos);
-                self.game.valid_moves =
-                    get_valid_moves(&self.game.board, clicked_pos, self.game.current_turn);
+        } else if let Some(piece) = self.game.board[row][col]
+            && piece.side == self.game.current_turn
+        {
+            self.game.selected_piece = Some(clicked_pos);
+            self.game.valid_moves =
+                get_valid_moves(&self.game.board, clicked_pos, self.game.current_turn);
+        }
+    }
+
+    /// Plays a move with animation and feedback, then hands over to the AI if it is its turn.
+    pub fn apply_move(&mut self, from: (usize, usize), to: (usize, usize)) {
+        let moving_piece = self.game.board[from.0][from.1];
+        let mover = self.game.current_turn;
+        let result = self.game.make_move(from, to);
+
+        if !matches!(result, MoveResult::Invalid) {
+            if let Some(piece) = moving_piece {
+                self.ui
+                    .piece_animations
+                    .push(PieceAnimation::new(piece, from, to));
+            }
+            if let Some(clock) = &mut self.ui.clock {
+                clock.complete_move(mover);
+            }
+        }
+
+        self.handle_move_result(result);
+        self.game.selected_piece = None;
+        self.game.valid_moves.clear();
+        self.check_ai_turn();
+    }
+
+    /// Plays the move typed into the move entry box.
+    pub fn handle_move_input(&mut self) {
+        let text = self.ui.move_input.trim().to_string();
+        if text.is_empty() || self.should_block_input() {
+            return;
+        }
+        match self.game.parse_move(&text) {
+            Ok(m) => {
+                self.apply_move(m.from, m.to);
+                self.ui.move_input.clear();
+            }
+            Err(e) => {
+                self.ui.popup =
+                    Some(PopupTip::new("误".to_string()).with_detail(format!("着法无效：{}", e)));
             }
         }
     }
@@ -78,29 +116,89 @@ impl ChessRealm {
             MoveResult::CaptureAndCheck(_piece) => {
                 self.ui.popup = Some(PopupTip::new("将".to_string()));
             }
-            MoveResult::Checkmate(winner) | MoveResult::Stalemate(winner) => {
-                let message = match winner {
-                    PieceSide::Red => "胜",
-                    PieceSide::Black => "负",
+            MoveResult::GameOver(status) => {
+                // Against the engine the result is the player's; otherwise it is Red's.
+                let side = match self.ui.window.game_mode {
+                    GameMode::PlayerVsAI => self.ui.human_side,
+                    GameMode::PlayerVsPlayer => PieceSide::Red,
                 };
-                self.ui.popup = Some(PopupTip::new_game_end(message.to_string()));
+                let message = match status.winner() {
+                    Some(winner) if winner == side => "胜",
+                    Some(_) => "负",
+                    None => "和",
+                };
+                let mut popup = PopupTip::new_game_end(message.to_string());
+                if let Some(reason) = status.reason() {
+                    popup = popup.with_detail(reason.label());
+                }
+                self.ui.popup = Some(popup);
             }
             MoveResult::Success | MoveResult::Invalid => {}
         }
     }
 
+    /// Saves the current game to a record file chosen by the user. A missing date and
+    /// player names are filled in for the file only.
+    pub fn handle_save(&mut self) {
+        let mut game = self.game.clone();
+        let metadata = &mut game.metadata;
+        if metadata.date.is_empty() {
+            metadata.date = record::today();
+        }
+        if metadata.red.is_empty() && metadata.black.is_empty() {
+            let (red, black) = match (self.ui.window.game_mode, self.ui.human_side) {
+                (GameMode::PlayerVsPlayer, _) => ("玩家", "玩家"),
+                (GameMode::PlayerVsAI, PieceSide::Red) => ("玩家", "引擎"),
+                (GameMode::PlayerVsAI, PieceSide::Black) => ("引擎", "玩家"),
+            };
+            metadata.red = red.to_string();
+            metadata.black = black.to_string();
+        }
+
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("棋谱", &[RECORD_EXTENSION])
+            .add_filter("PGN", &[PGN_EXTENSION])
+            .set_file_name(format!("{}.{}", metadata.date, RECORD_EXTENSION))
+            .save_file()
+        else {
+            return;
+        };
+        if let Err(e) = record::save_game(&game, &path, self.ui.window.notation) {
+            self.ui.popup =
+                Some(PopupTip::new("误".to_string()).with_detail(format!("保存失败：{}", e)));
+        }
+    }
+
+    /// Loads a game from a record file chosen by the user.
+    pub fn handle_open(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("棋谱", &[RECORD_EXTENSION, PGN_EXTENSION, XQF_EXTENSION])
+            .pick_file()
+        else {
+            return;
+        };
+        match record::load_game(&path) {
+            Ok(game) => self.start_game(game),
+            Err(e) => {
+                self.ui.popup = Some(
+                    PopupTip::new("误".to_string()).with_detail(format!("无法打开棋谱：{}", e)),
+                );
+            }
+        }
+    }
+
     /// Handles the undo button click.
     pub fn handle_undo(&mut self) {
         match self.ui.window.game_mode {
             GameMode::PlayerVsPlayer => {
-                if let Some(undone_move) = self.game.undo_last_move() {
-                    if let Some(piece) = self.game.board[undone_move.from.0][undone_move.from.1] {
-                        self.ui.piece_animations.push(PieceAnimation::new(
-                            piece,
-                            undone_move.to,
-                            undone_move.from,
-                        ));
-                    }
+                if let Some(undone_move) = self.game.undo_last_move()
+                    && let Some(piece) = self.game.board[undone_move.from.0][undone_move.from.1]
+                {
+                    self.ui.piece_animations.push(PieceAnimation::new(
+                        piece,
+                        undone_move.to,
+                        undone_move.from,
+                    ));
                 }
             }
             GameMode::PlayerVsAI => {
@@ -137,12 +235,146 @@ impl ChessRealm {
         }
     }
 
+    /// Handles the redo button, replaying what the undo button took back.
+    pub fn handle_redo(&mut self) {
+        if self.should_block_input() {
+            return;
+        }
+        let plies = match self.ui.window.game_mode {
+            GameMode::PlayerVsPlayer => 1,
+            GameMode::PlayerVsAI => 2,
+        };
+        for _ in 0..plies {
+            let Some(m) = self.game.go_forward() else {
+                break;
+            };
+            if let Some(piece) = self.game.board[m.to.0][m.to.1] {
+                self.ui
+                    .piece_animations
+                    .push(PieceAnimation::new(piece, m.from, m.to));
+            }
+        }
+        self.check_ai_turn();
+    }
+
+    /// Moves to the start of the game.
+    pub fn handle_first(&mut self) {
+        self.jump_to(ROOT);
+    }
+
+    /// Moves to the end of the current line.
+    pub fn handle_last(&mut self) {
+        if self.should_block_input() {
+            return;
+        }
+        if self.game.go_to_end() {
+            self.check_ai_turn();
+        }
+    }
+
+    /// Moves to a node of the game tree, keeping every line played.
+    pub fn jump_to(&mut self, node: NodeId) {
+        if self.should_block_input() {
+            return;
+        }
+        if self.game.go_to(node) {
+            self.check_ai_turn();
+        }
+    }
+
+    /// Deletes the line ending in the current position, stepping back to where it branched.
+    pub fn delete_current_branch(&mut self) {
+        if self.should_block_input() {
+            return;
+        }
+        if self.game.delete_branch(self.game.current) {
+            self.check_ai_turn();
+        }
+    }
+
+    /// Resigns the game for the player, or for the side to move between two players. The
+    /// player may resign while the engine is thinking.
+    pub fn handle_resign(&mut self) {
+        if self.ui.editor.is_some() || self.ui.draw_offer.is_some() || self.game.status.is_over() {
+            return;
+        }
+        let side = match self.ui.window.game_mode {
+            GameMode::PlayerVsAI => self.ui.human_side,
+            GameMode::PlayerVsPlayer => self.game.current_turn,
+        };
+        self.game.resign(side);
+        self.handle_move_result(MoveResult::GameOver(self.game.status));
+    }
+
+    /// Offers a draw. The engine answers at once, accepting only if its last score says it
+    /// stands worse; another player answers in the draw offer dialog. The engine declines
+    /// while it has no score for the game, e.g. before its first move, rather than searching
+    /// just to answer.
+    pub fn handle_offer_draw(&mut self) {
+        if self.should_block_input() || self.game.status.is_over() {
+            return;
+        }
+        match self.ui.window.game_mode {
+            GameMode::PlayerVsAI => match self.ui.engine_score {
+                Some(score) if score.accepts_draw() => {
+                    self.game.agree_draw();
+                    self.handle_move_result(MoveResult::GameOver(self.game.status));
+                }
+                Some(_) => {
+                    self.ui.popup =
+                        Some(PopupTip::new("拒".to_string()).with_detail("引擎拒绝和棋"));
+                }
+                None => {
+                    self.ui.popup = Some(
+                        PopupTip::new("拒".to_string()).with_detail("引擎尚未评估局面，拒绝和棋"),
+                    );
+                }
+            },
+            GameMode::PlayerVsPlayer => self.ui.draw_offer = Some(self.game.current_turn),
+        }
+    }
+
+    /// Answers the pending draw offer.
+    pub fn answer_draw_offer(&mut self, accept: bool) {
+        if self.ui.draw_offer.take().is_none() {
+            return;
+        }
+        if accept {
+            self.game.agree_draw();
+            self.handle_move_result(MoveResult::GameOver(self.game.status));
+        } else {
+            self.ui.popup = Some(PopupTip::new("拒".to_string()).with_detail("对方拒绝和棋"));
+        }
+    }
+
+    /// Claims a draw by repetition or the move limit, or explains that the claim is refused.
+    pub fn handle_claim_draw(&mut self) {
+        if self.should_block_input() || self.game.status.is_over() {
+            return;
+        }
+        if self.game.claim_draw() {
+            self.handle_move_result(MoveResult::GameOver(self.game.status));
+        } else {
+            self.ui.popup = Some(PopupTip::new("误".to_string()).with_detail("不符合和棋条件"));
+        }
+    }
+
+    /// Returns true if the engine is to move in a game against it that is not over.
+    pub fn is_ai_turn(&self) -> bool {
+        self.ui.window.game_mode == GameMode::PlayerVsAI
+            && self.game.current_turn != self.ui.human_side
+            && !self.game.status.is_over()
+    }
+
     /// Returns true if player input should be blocked.
     pub fn should_block_input(&self) -> bool {
-        if self.ui.ai_thinking {
+        if self.ui.editor.is_some() {
             return true;
         }
-        if self.ui.window.game_mode == GameMode::PlayerVsAI && self.game.is_ai_turn() {
+        if self.ui.ai_thinking || self.ui.draw_offer.is_some() {
+            return true;
+        }
+        if self.is_ai_turn() {
             return true;
         }
         if !self.ui.piece_animations.is_empty() {
@@ -153,10 +385,7 @@ impl ChessRealm {
 
     /// Checks if it's AI's turn and sets the thinking flag.
     pub fn check_ai_turn(&mut self) {
-        if self.ui.window.game_mode == GameMode::PlayerVsAI
-            && self.game.is_ai_turn()
-            && self.game.status == crate::game::state::GameStatus::InProgress
-        {
+        if self.is_ai_turn() {
             self.ui.ai_thinking = true;
         }
     }
@@ -1,7 +1,11 @@
+pub mod analysis;
 pub mod app;
 pub mod board;
+pub mod clock;
+pub mod engine_options;
 pub mod fonts;
 pub mod input;
+pub mod moves;
 pub mod settings;
 pub mod state;
 pub mod theme;
@@ -0,0 +1,190 @@
+use crate::game::{board, notation, piece::PieceSide, state::GameState, tree::NodeId};
+use crate::ui::app::ChessRealm;
+use crate::ui::fonts::font;
+use eframe::egui;
+
+/// A round of the move list: its number and the Red and Black moves with their nodes.
+type MoveRow = (u32, [Option<(NodeId, String)>; 2]);
+
+impl ChessRealm {
+    /// Shows the clocks, the move list of the current line, the continuations of the current
+    /// position, the controls for reordering and deleting the current line, and its comment.
+    pub fn render_moves_panel(&mut self, ctx: &egui::Context, font_size: f32) {
+        egui::SidePanel::right("moves")
+            .resizable(false)
+            .exact_width(font_size * 10.0)
+            .show(ctx, |ui| {
+                ui.add_enabled_ui(!self.should_block_input(), |ui| {
+                    ui.add_space(8.0);
+                    if self.ui.clock.is_some() {
+                        self.render_clocks(ui, font_size * 0.8);
+                        ui.separator();
+                    }
+                    ui.label(font("棋谱", "zhuque-fangsong", font_size));
+                    ui.separator();
+                    self.render_move_list(ui, font_size * 0.8);
+
+                    ui.separator();
+                    ui.label(font("变着", "zhuque-fangsong", font_size));
+                    self.render_variations(ui, font_size * 0.8);
+
+                    ui.separator();
+                    ui.label(font("注释", "zhuque-fangsong", font_size * 0.8));
+                    let current = self.game.current;
+                    let mut comment = self
+                        .game
+                        .tree
+                        .node(current)
+                        .comment
+                        .clone()
+                        .unwrap_or_default();
+                    let editor = ui.add(
+                        egui::TextEdit::multiline(&mut comment)
+                            .font(egui::FontId::new(
+                                font_size * 0.8,
+                                egui::FontFamily::Name("zhuque-fangsong".into()),
+                            ))
+                            .desired_rows(3)
+                            .desired_width(f32::INFINITY),
+                    );
+                    if editor.changed() {
+                        self.game.tree.set_comment(current, Some(comment));
+                    }
+                });
+            });
+    }
+
+    /// Lists the moves leading to the current position and its main continuation, one round
+    /// per row. Clicking a move jumps to the position after it.
+    fn render_move_list(&mut self, ui: &mut egui::Ui, font_size: f32) {
+        let tree = &self.game.tree;
+        let mut nodes = tree.path(self.game.current);
+        nodes.remove(0);
+        nodes.extend(tree.main_line(self.game.current));
+
+        let (mut board, mut number, mut side) = match &self.game.start_fen {
+            Some(fen) => {
+                let start = GameState::from_fen(fen).unwrap_or_default();
+                (start.board, start.fullmove_number, start.current_turn)
+            }
+            None => (board::init_board(), 1, PieceSide::Red),
+        };
+        let mut rows: Vec<MoveRow> = Vec::new();
+        for id in nodes {
+            let Some(m) = tree.node(id).mv else {
+                continue;
+            };
+            let text = notation::format_move(&board, &m, self.ui.window.notation)
+                .unwrap_or_else(|| GameState::move_to_uci(&m));
+            board[m.to.0][m.to.1] = board[m.from.0][m.from.1].take();
+
+            let column = usize::from(side == PieceSide::Black);
+            if column == 0 || rows.last().is_none_or(|(n, _)| *n != number) {
+                rows.push((number, [None, None]));
+            }
+            if let Some((_, row)) = rows.last_mut() {
+                row[column] = Some((id, text));
+            }
+            if side == PieceSide::Black {
+                number += 1;
+            }
+            side = side.opponent();
+        }
+
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .max_height(ui.available_height() * 0.5)
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                egui::Grid::new("move_list")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (number, row) in rows {
+                            ui.label(font(format!("{}.", number), "zhuque-fangsong", font_size));
+                            for cell in row {
+                                match cell {
+                                    Some((id, text)) => {
+                                        let selected = id == self.game.current;
+                                        let response = ui.selectable_label(
+                                            selected,
+                                            font(text, "zhuque-fangsong", font_size),
+                                        );
+                                        if selected && self.ui.move_list_node != Some(id) {
+                                            response.scroll_to_me(None);
+                                            self.ui.move_list_node = Some(id);
+                                        }
+                                        if response.clicked() {
+                                            clicked = Some(id);
+                                        }
+                                    }
+                                    None => {
+                                        ui.label("");
+                                    }
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        if let Some(id) = clicked {
+            self.jump_to(id);
+        }
+    }
+
+    /// Lists the continuations of the current position with buttons to play them, and the
+    /// controls for reordering and deleting the line ending in the current position.
+    fn render_variations(&mut self, ui: &mut egui::Ui, font_size: f32) {
+        let notation = self.ui.window.notation;
+        let children = self.game.tree.node(self.game.current).children.clone();
+        if children.is_empty() {
+            ui.label(font("（无后续着法）", "zhuque-fangsong", font_size));
+        }
+        ui.horizontal_wrapped(|ui| {
+            for (index, child) in children.into_iter().enumerate() {
+                let Some(m) = self.game.tree.node(child).mv else {
+                    continue;
+                };
+                let text = self
+                    .game
+                    .format_move(&m, notation)
+                    .unwrap_or_else(|| GameState::move_to_uci(&m));
+                let label = if index == 0 {
+                    format!("主 {}", text)
+                } else {
+                    format!("{} {}", index, text)
+                };
+                if ui
+                    .button(font(label, "zhuque-fangsong", font_size))
+                    .clicked()
+                {
+                    self.jump_to(child);
+                }
+            }
+        });
+
+        let current = self.game.current;
+        if self.game.tree.node(current).parent.is_some() {
+            ui.horizontal_wrapped(|ui| {
+                if ui
+                    .button(font("提升", "zhuque-fangsong", font_size))
+                    .clicked()
+                {
+                    self.game.tree.promote(current);
+                }
+                if ui
+                    .button(font("降低", "zhuque-fangsong", font_size))
+                    .clicked()
+                {
+                    self.game.tree.demote(current);
+                }
+                if ui
+                    .button(font("删除", "zhuque-fangsong", font_size))
+                    .clicked()
+                {
+                    self.delete_current_branch();
+                }
+            });
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use crate::engine::uci::EngineHandle;
+use crate::game::clock::ClockKind;
 use crate::ui::app::ChessRealm;
 use crate::ui::fonts::font;
 use crate::ui::theme::Theme;
 use eframe::egui;
+use std::ops::RangeInclusive;
 
 impl ChessRealm {
     pub fn render_settings_window(&mut self, ctx: &egui::Context) {
@@ -12,7 +14,7 @@ impl ChessRealm {
 
         let dark_mode = self.ui.window.dark_mode;
 
-        let settings_size = [400.0_f32, 300.0_f32];
+        let settings_size = [400.0_f32, 560.0_f32];
         let position = ctx.input(|i| i.viewport().outer_rect).map(|rect| {
             let center = rect.center();
             egui::pos2(
@@ -57,115 +59,271 @@ impl ChessRealm {
                     });
 
                 egui::CentralPanel::default().show(ctx, |ui| {
-                    ui.add_space(10.0);
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        ui.add_space(10.0);
 
-                    let row_height = 36.0;
+                        let row_height = 36.0;
 
-                    ui.horizontal(|ui| {
-                        ui.set_min_height(row_height);
-                        ui.add_space(20.0);
-                        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
-                            ui.label(font("主题", "zhuque-fangsong", 16.0));
-                        });
-                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.horizontal(|ui| {
+                            ui.set_min_height(row_height);
                             ui.add_space(20.0);
-                            let theme_text = if dark_mode { "深色" } else { "浅色" };
-                            if ui
-                                .button(font(theme_text, "zhuque-fangsong", 16.0))
-                                .clicked()
-                            {
-                                self.ui.window.dark_mode = !self.ui.window.dark_mode;
-                            }
+                            ui.with_layout(
+                                egui::Layout::left_to_right(egui::Align::Center),
+                                |ui| {
+                                    ui.label(font("主题", "zhuque-fangsong", 16.0));
+                                },
+                            );
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    ui.add_space(20.0);
+                                    let theme_text = if dark_mode { "深色" } else { "浅色" };
+                                    if ui
+                                        .button(font(theme_text, "zhuque-fangsong", 16.0))
+                                        .clicked()
+                                    {
+                                        self.ui.window.dark_mode = !self.ui.window.dark_mode;
+                                    }
+                                },
+                            );
                         });
-                    });
 
-                    ui.add_space(10.0);
+                        ui.add_space(10.0);
 
-                    if self.ui.engine.is_some() && !self.ui.engine_invalid {
                         ui.horizontal(|ui| {
                             ui.set_min_height(row_height);
                             ui.add_space(20.0);
                             ui.with_layout(
                                 egui::Layout::left_to_right(egui::Align::Center),
                                 |ui| {
-                                    ui.label(font("引擎棋力等级", "zhuque-fangsong", 16.0));
+                                    ui.label(font("自然限着", "zhuque-fangsong", 16.0));
                                 },
                             );
                             ui.with_layout(
                                 egui::Layout::right_to_left(egui::Align::Center),
                                 |ui| {
                                     ui.add_space(20.0);
-                                    ui.label(font(
-                                        &format!("{}", self.ui.window.engine_elo),
-                                        "zhuque-fangsong",
-                                        16.0,
-                                    ));
+                                    let limit_text = match self.ui.window.move_limit() {
+                                        Some(limit) => format!("{}回合", limit),
+                                        None => "关".to_string(),
+                                    };
+                                    ui.label(font(limit_text, "zhuque-fangsong", 16.0));
                                     ui.add_space(10.0);
-                                    let mut elo = self.ui.window.engine_elo as f32;
+                                    let mut limit = self.ui.window.move_limit as f32;
                                     if ui
                                         .add(
-                                            egui::Slider::new(&mut elo, 1300.0..=3000.0)
+                                            egui::Slider::new(&mut limit, 0.0..=120.0)
                                                 .show_value(false),
                                         )
                                         .changed()
                                     {
-                                        self.ui.window.engine_elo = elo as u32;
+                                        self.ui.window.move_limit = limit as u32;
+                                        self.game.move_limit = self.ui.window.move_limit();
                                     }
                                 },
                             );
                         });
 
                         ui.add_space(10.0);
-                    }
 
-                    ui.horizontal(|ui| {
-                        ui.set_min_height(row_height);
-                        ui.add_space(20.0);
-                        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
-                            ui.label(font("引擎路径", "zhuque-fangsong", 16.0));
+                        ui.horizontal(|ui| {
+                            ui.set_min_height(row_height);
+                            ui.add_space(20.0);
+                            ui.with_layout(
+                                egui::Layout::left_to_right(egui::Align::Center),
+                                |ui| {
+                                    ui.label(font("记谱", "zhuque-fangsong", 16.0));
+                                },
+                            );
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    ui.add_space(20.0);
+                                    let notation = self.ui.window.notation;
+                                    if ui
+                                        .button(font(notation.label(), "zhuque-fangsong", 16.0))
+                                        .clicked()
+                                    {
+                                        self.ui.window.notation = notation.next();
+                                    }
+                                },
+                            );
                         });
-                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+
+                        ui.add_space(10.0);
+
+                        ui.horizontal(|ui| {
+                            ui.set_min_height(row_height);
                             ui.add_space(20.0);
-                            if ui.button(font("选择", "zhuque-fangsong", 16.0)).clicked() {
-                                if let Some(path) = rfd::FileDialog::new().pick_file() {
-                                    let path_str = path.display().to_string();
-                                    match EngineHandle::new(&path_str) {
-                                        Ok(engine) => {
-                                            self.ui.window.engine_path = Some(path_str);
-                                            self.ui.engine = Some(engine);
-                                            self.ui.engine_invalid = false;
+                            ui.with_layout(
+                                egui::Layout::left_to_right(egui::Align::Center),
+                                |ui| {
+                                    ui.label(font("计时", "zhuque-fangsong", 16.0));
+                                },
+                            );
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    ui.add_space(20.0);
+                                    let kind = self.ui.window.time_control.kind;
+                                    if ui
+                                        .button(font(kind.label(), "zhuque-fangsong", 16.0))
+                                        .clicked()
+                                    {
+                                        self.ui.window.time_control.kind = kind.next();
+                                    }
+                                },
+                            );
+                        });
+
+                        ui.add_space(10.0);
+
+                        let control = &mut self.ui.window.time_control;
+                        if control.is_enabled() {
+                            let mut minutes = control.base_secs / 60;
+                            let text = format!("{}分钟", minutes);
+                            if slider_row(ui, "基本用时", &mut minutes, 1..=180, text) {
+                                control.base_secs = minutes * 60;
+                            }
+                        }
+                        if control.kind == ClockKind::Fischer {
+                            let text = format!("{}秒", control.increment_secs);
+                            slider_row(ui, "每步加秒", &mut control.increment_secs, 0..=60, text);
+                        }
+                        if control.kind == ClockKind::ByoYomi {
+                            let text = format!("{}秒", control.period_secs);
+                            slider_row(ui, "读秒时长", &mut control.period_secs, 10..=300, text);
+                            let text = format!("{}步", control.period_moves);
+                            slider_row(ui, "每次步数", &mut control.period_moves, 1..=30, text);
+                            let text = format!("{}次", control.periods);
+                            slider_row(ui, "读秒次数", &mut control.periods, 1..=10, text);
+                        }
+
+                        if self.ui.engine.is_some() && !self.ui.engine_invalid {
+                            ui.horizontal(|ui| {
+                                ui.set_min_height(row_height);
+                                ui.add_space(20.0);
+                                ui.with_layout(
+                                    egui::Layout::left_to_right(egui::Align::Center),
+                                    |ui| {
+                                        ui.label(font("引擎棋力等级", "zhuque-fangsong", 16.0));
+                                    },
+                                );
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        ui.add_space(20.0);
+                                        ui.label(font(
+                                            format!("{}", self.ui.window.engine_elo),
+                                            "zhuque-fangsong",
+                                            16.0,
+                                        ));
+                                        ui.add_space(10.0);
+                                        let mut elo = self.ui.window.engine_elo as f32;
+                                        if ui
+                                            .add(
+                                                egui::Slider::new(&mut elo, 1300.0..=3000.0)
+                                                    .show_value(false),
+                                            )
+                                            .changed()
+                                        {
+                                            self.ui.window.engine_elo = elo as u32;
+                                        }
+                                    },
+                                );
+                            });
+
+                            ui.add_space(10.0);
+
+                            let lines = &mut self.ui.window.analysis_lines;
+                            let text = format!("{}条", lines);
+                            slider_row(ui, "分析变例", lines, 1..=5, text);
+                            ui.horizontal(|ui| {
+                                ui.set_min_height(row_height);
+                                ui.add_space(20.0);
+                                ui.with_layout(
+                                    egui::Layout::left_to_right(egui::Align::Center),
+                                    |ui| {
+                                        ui.label(font("引擎参数", "zhuque-fangsong", 16.0));
+                                    },
+                                );
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        ui.add_space(20.0);
+                                        if ui
+                                            .button(font("设置", "zhuque-fangsong", 16.0))
+                                            .clicked()
+                                        {
+                                            self.ui.window.show_engine_options = true;
                                         }
-                                        Err(_) => {
-                                            self.ui.window.engine_path = None;
-                                            self.ui.engine = None;
-                                            self.ui.engine_invalid = true;
+                                    },
+                                );
+                            });
+
+                            ui.add_space(10.0);
+                        }
+
+                        ui.horizontal(|ui| {
+                            ui.set_min_height(row_height);
+                            ui.add_space(20.0);
+                            ui.with_layout(
+                                egui::Layout::left_to_right(egui::Align::Center),
+                                |ui| {
+                                    ui.label(font("引擎路径", "zhuque-fangsong", 16.0));
+                                },
+                            );
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    ui.add_space(20.0);
+                                    if ui.button(font("选择", "zhuque-fangsong", 16.0)).clicked()
+                                        && let Some(path) = rfd::FileDialog::new().pick_file()
+                                    {
+                                        let path_str = path.display().to_string();
+                                        match EngineHandle::new(&path_str) {
+                                            Ok(engine) => {
+                                                self.ui.window.engine_path = Some(path_str);
+                                                self.ui.engine = Some(engine);
+                                                self.ui.engine_invalid = false;
+                                                self.ui.analysis = None;
+                                                self.apply_engine_options();
+                                            }
+                                            Err(_) => {
+                                                self.ui.window.engine_path = None;
+                                                self.ui.engine = None;
+                                                self.ui.engine_invalid = true;
+                                            }
                                         }
                                     }
-                                }
-                            }
-                            if self.ui.window.engine_path.is_some() {
-                                if ui.button(font("清除", "zhuque-fangsong", 16.0)).clicked() {
-                                    self.ui.window.engine_path = None;
-                                    self.ui.engine = None;
-                                    self.ui.engine_invalid = false;
-                                    self.ui.window.game_mode =
-                                        crate::ui::state::GameMode::PlayerVsPlayer;
-                                }
-                            }
+                                    if self.ui.window.engine_path.is_some()
+                                        && ui
+                                            .button(font("清除", "zhuque-fangsong", 16.0))
+                                            .clicked()
+                                    {
+                                        self.ui.window.engine_path = None;
+                                        self.ui.engine = None;
+                                        self.ui.engine_invalid = false;
+                                        self.ui.window.game_mode =
+                                            crate::ui::state::GameMode::PlayerVsPlayer;
+                                    }
+                                },
+                            );
                         });
-                    });
 
-                    ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
-                        ui.add_space(20.0);
-                        if self.ui.engine_invalid {
-                            let theme = Theme::from_dark_mode(self.ui.window.dark_mode);
-                            ui.label(
-                                font("引擎无效", "zhuque-fangsong", 14.0).color(theme.status.error),
-                            );
-                        } else if let Some(ref path) = self.ui.window.engine_path {
-                            let display_path = truncate_path_display(path, 35);
-                            ui.label(font(display_path, "zhuque-fangsong", 14.0));
-                        }
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
+                            ui.add_space(20.0);
+                            if self.ui.engine_invalid {
+                                let theme = Theme::from_dark_mode(self.ui.window.dark_mode);
+                                ui.label(
+                                    font("引擎无效", "zhuque-fangsong", 14.0)
+                                        .color(theme.status.error),
+                                );
+                            } else if let Some(ref path) = self.ui.window.engine_path {
+                                let display_path = truncate_path_display(path, 35);
+                                ui.label(font(display_path, "zhuque-fangsong", 14.0));
+                            }
+                        });
                     });
                 });
 
@@ -188,3 +346,32 @@ fn truncate_path_display(path: &str, max_chars: usize) -> String {
         format!("...{}", truncated)
     }
 }
+
+/// Shows a settings row with a slider for a whole number, written out as `text`. Returns true
+/// if the value changed.
+fn slider_row(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut u32,
+    range: RangeInclusive<u32>,
+    text: String,
+) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.set_min_height(36.0);
+        ui.add_space(20.0);
+        ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
+            ui.label(font(label, "zhuque-fangsong", 16.0));
+        });
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.add_space(20.0);
+            ui.label(font(text, "zhuque-fangsong", 16.0));
+            ui.add_space(10.0);
+            changed = ui
+                .add(egui::Slider::new(value, range).show_value(false))
+                .changed();
+        });
+    });
+    ui.add_space(10.0);
+    changed
+}
@@ -1,7 +1,15 @@
 use crate::constants::APP_DEFAULT_SIZE;
-use crate::engine::uci::EngineHandle;
-use crate::game::piece::Piece;
+use crate::engine::uci::{EngineHandle, Score, SearchProgress};
+use crate::game::board::BoardState;
+use crate::game::clock::{GameClock, TimeControl};
+use crate::game::fen::{self, FenError};
+use crate::game::notation::Notation;
+use crate::game::piece::{Piece, PieceSide};
+use crate::game::state::DEFAULT_MOVE_LIMIT;
+use crate::game::tree::NodeId;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hasher, RandomState};
 use std::time::Instant;
 
 /// Game mode: Player vs Player or Player vs AI
@@ -28,6 +36,51 @@ impl GameMode {
     }
 }
 
+/// Which side the player takes against the engine.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlayerColor {
+    #[default]
+    Red,
+    Black,
+    /// Drawn anew for every game.
+    Random,
+}
+
+impl PlayerColor {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PlayerColor::Red => "执红",
+            PlayerColor::Black => "执黑",
+            PlayerColor::Random => "随机",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            PlayerColor::Red => PlayerColor::Black,
+            PlayerColor::Black => PlayerColor::Random,
+            PlayerColor::Random => PlayerColor::Red,
+        }
+    }
+
+    /// Returns the side the player takes, drawing one for `Random`.
+    pub fn resolve(&self) -> PieceSide {
+        match self {
+            PlayerColor::Red => PieceSide::Red,
+            PlayerColor::Black => PieceSide::Black,
+            PlayerColor::Random => {
+                // RandomState is seeded from the OS, and each instance hashes differently.
+                let bits = RandomState::new().build_hasher().finish();
+                if bits.is_multiple_of(2) {
+                    PieceSide::Red
+                } else {
+                    PieceSide::Black
+                }
+            }
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct WindowState {
     pub width: f32,
@@ -36,8 +89,28 @@ pub struct WindowState {
     pub engine_path: Option<String>,
     pub game_mode: GameMode,
     pub engine_elo: u32,
+    /// Full moves without a capture before a draw is declared; 0 disables the rule.
+    #[serde(default = "default_move_limit")]
+    pub move_limit: u32,
+    /// Notation used to show moves.
+    #[serde(default)]
+    pub notation: Notation,
+    /// Side the player takes against the engine.
+    #[serde(default)]
+    pub player_color: PlayerColor,
+    /// Number of best lines shown in analysis mode.
+    #[serde(default = "default_analysis_lines")]
+    pub analysis_lines: u32,
+    /// Time control of new games.
+    #[serde(default)]
+    pub time_control: TimeControl,
+    /// Option values set by the user, by engine path and option name.
+    #[serde(default)]
+    pub engine_options: BTreeMap<String, BTreeMap<String, String>>,
     #[serde(skip)]
     pub show_settings: bool,
+    #[serde(skip)]
+    pub show_engine_options: bool,
 }
 
 impl Default for WindowState {
@@ -49,13 +122,37 @@ impl Default for WindowState {
             engine_path: None,
             game_mode: GameMode::default(),
             engine_elo: 3000,
+            move_limit: DEFAULT_MOVE_LIMIT,
+            notation: Notation::default(),
+            player_color: PlayerColor::default(),
+            analysis_lines: default_analysis_lines(),
+            time_control: TimeControl::default(),
+            engine_options: BTreeMap::new(),
             show_settings: false,
+            show_engine_options: false,
         }
     }
 }
 
+fn default_move_limit() -> u32 {
+    DEFAULT_MOVE_LIMIT
+}
+
+fn default_analysis_lines() -> u32 {
+    3
+}
+
+impl WindowState {
+    /// Returns the configured move limit, or None if the rule is disabled.
+    pub fn move_limit(&self) -> Option<u32> {
+        (self.move_limit > 0).then_some(self.move_limit)
+    }
+}
+
 pub struct PopupTip {
     pub message: String,
+    /// Smaller text shown below the popup, e.g. why the game ended.
+    pub detail: Option<String>,
     pub shown_at: Instant,
     pub duration_secs: f32,
     pub use_dark_red: bool,
@@ -65,6 +162,7 @@ impl PopupTip {
     pub fn new(message: String) -> Self {
         Self {
             message,
+            detail: None,
             shown_at: Instant::now(),
             duration_secs: 1.0,
             use_dark_red: false,
@@ -74,12 +172,18 @@ impl PopupTip {
     pub fn new_game_end(message: String) -> Self {
         Self {
             message,
+            detail: None,
             shown_at: Instant::now(),
             duration_secs: 3.0,
             use_dark_red: true,
         }
     }
 
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
     pub fn is_visible(&self) -> bool {
         self.shown_at.elapsed().as_secs_f32() < self.duration_secs
     }
@@ -113,26 +217,86 @@ impl PieceAnimation {
     }
 }
 
+/// A position being set up in the board editor.
+pub struct BoardEditor {
+    pub board: BoardState,
+    pub side: PieceSide,
+    /// Piece placed by clicking a square, or None to remove pieces instead.
+    pub brush: Option<Piece>,
+    /// Square of the piece being dragged across the board.
+    pub drag_from: Option<(usize, usize)>,
+    /// Contents of the FEN box.
+    pub fen_input: String,
+}
+
+impl BoardEditor {
+    pub fn new(board: BoardState, side: PieceSide) -> Self {
+        let mut editor = Self {
+            board,
+            side,
+            brush: None,
+            drag_from: None,
+            fen_input: String::new(),
+        };
+        editor.fen_input = editor.to_fen();
+        editor
+    }
+
+    pub fn to_fen(&self) -> String {
+        fen::to_fen(&self.board, self.side, 0, 1)
+    }
+
+    /// Replaces the position with the one described by a FEN string.
+    pub fn load_fen(&mut self, text: &str) -> Result<(), FenError> {
+        let parsed = fen::parse_fen(text.trim())?;
+        self.board = parsed.board;
+        self.side = parsed.side;
+        self.fen_input = self.to_fen();
+        Ok(())
+    }
+
+    /// Puts a piece on a square, or empties it.
+    pub fn set(&mut self, (row, col): (usize, usize), piece: Option<Piece>) {
+        self.board[row][col] = piece;
+        self.fen_input = self.to_fen();
+    }
+}
+
+/// Infinite analysis of the position on the board.
+#[derive(Default)]
+pub struct Analysis {
+    /// Id of the search under way; info from earlier searches is dropped.
+    pub search_id: u64,
+    /// Root FEN and moves of the position being searched, or None before the first search.
+    pub position: Option<(Option<String>, String)>,
+    /// Number of best lines being searched.
+    pub multipv: u32,
+    /// What the current search has found so far.
+    pub progress: SearchProgress,
+}
+
 pub struct UiState {
     pub window: WindowState,
+    /// Side the player takes in the current game against the engine.
+    pub human_side: PieceSide,
+    /// Clocks of the current game, or None if it is untimed.
+    pub clock: Option<GameClock>,
     pub popup: Option<PopupTip>,
     pub engine: Option<EngineHandle>,
     pub engine_invalid: bool,
     pub ai_thinking: bool,
     pub ai_request_sent: bool,
+    /// Last score the engine reported for its own side.
+    pub engine_score: Option<Score>,
+    /// Side whose draw offer awaits the other player's answer.
+    pub draw_offer: Option<PieceSide>,
+    /// The engine's analysis of the board, while analysis mode is on.
+    pub analysis: Option<Analysis>,
     pub piece_animations: Vec<PieceAnimation>,
-}
-
-impl Default for UiState {
-    fn default() -> Self {
-        Self {
-            window: WindowState::default(),
-            popup: None,
-            engine: None,
-            engine_invalid: false,
-            ai_thinking: false,
-            ai_request_sent: false,
-            piece_animations: Vec::new(),
-        }
-    }
+    /// Text typed into the move entry box.
+    pub move_input: String,
+    /// Node the move list last scrolled to, so it only follows the board when it changes.
+    pub move_list_node: Option<NodeId>,
+    /// The board editor, while it is open.
+    pub editor: Option<BoardEditor>,
 }
@@ -14,6 +14,8 @@ pub struct HighlightColors {
     pub selected_piece: Color32,
     pub last_move: Color32,
     pub valid_move: Color32,
+    /// Arrows of the engine's candidate moves in analysis mode.
+    pub analysis_arrow: Color32,
 }
 
 /// Colors for popup messages.
@@ -52,6 +54,7 @@ impl Theme {
                 selected_piece: Color32::from_rgb(255, 215, 0),
                 last_move: Color32::from_rgba_unmultiplied(255, 200, 0, 120),
                 valid_move: Color32::from_rgba_unmultiplied(0, 200, 0, 180),
+                analysis_arrow: Color32::from_rgb(60, 140, 230),
             },
             popup: PopupColors {
                 background: Color32::from_rgba_unmultiplied(255, 255, 255, 180),
@@ -76,6 +79,7 @@ impl Theme {
                 selected_piece: Color32::from_rgb(255, 215, 0),
                 last_move: Color32::from_rgba_unmultiplied(255, 200, 0, 120),
                 valid_move: Color32::from_rgba_unmultiplied(0, 200, 0, 180),
+                analysis_arrow: Color32::from_rgb(60, 140, 230),
             },
             popup: PopupColors {
                 background: Color32::from_rgba_unmultiplied(255, 255, 255, 180),